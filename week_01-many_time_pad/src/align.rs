@@ -0,0 +1,54 @@
+// Relative alignment of ciphertexts encrypted with the same keystream
+// but starting at different positions in it (e.g. nonce reuse where each
+// message continues the stream of the previous one).
+//
+// At the right shift the xor of two ciphertexts is the xor of two
+// plaintexts: under a plaintext model, a byte x shows up there with
+// probability P_xor(x) = sum_a P(a) P(a ^ x), while at a wrong shift it
+// is uniformly random. The log-likelihood ratio of the overlap between
+// the two hypotheses tells how plaintext-like the xor is: positive on
+// average at the right shift, negative at any other.
+
+use crate::detect::{self, overlap_xor};
+use crate::PlaintextModel;
+
+// log P_xor(x) - log(1/256) for every byte x
+pub fn xor_table(model: &dyn PlaintextModel) -> [f64; 256] {
+    let scores: Vec<f64> = (0..=255u8).map(|b| model.score(b)).collect();
+    let max = scores.iter().cloned().fold(f64::MIN, f64::max);
+    let weights: Vec<f64> = scores.iter().map(|s| (s - max).exp()).collect();
+    let total: f64 = weights.iter().sum();
+    let p: Vec<f64> = weights.iter().map(|w| w / total).collect();
+
+    let mut table = [0.0; 256];
+    for (x, t) in table.iter_mut().enumerate() {
+        let p_xor: f64 = (0..256).map(|a| p[a] * p[a ^ x]).sum();
+        *t = (p_xor * 256.0).ln();
+    }
+    table
+}
+
+// Total log-likelihood ratio of the overlap of c_i and c_j at shift
+pub fn pair_llr(ci: &[u8], cj: &[u8], shift: isize, table: &[f64; 256]) -> (usize, f64) {
+    let xor = overlap_xor(ci, cj, shift);
+    (xor.len(), xor.iter().map(|&x| table[x as usize]).sum())
+}
+
+// Keystream offset of every ciphertext, assuming they all share one
+// keystream and start within max_shift bytes of each other.
+// The best shift of each pair is linked strongest first, as long as it
+// looks more like plaintext than like noise; a ciphertext that cannot be
+// linked to any other is left at offset 0, as is the earliest one.
+pub fn align(ciphertexts: &[Vec<u8>], max_shift: usize, min_overlap: usize, model: &dyn PlaintextModel) -> Vec<usize> {
+    let table = xor_table(model);
+    let stats = detect::pair_stats_by(ciphertexts, max_shift, min_overlap,
+                                      |ci, cj, shift| pair_llr(ci, cj, shift, &table));
+
+    let mut offsets = vec![0; ciphertexts.len()];
+    for cluster in detect::clusters(ciphertexts.len(), &stats, f64::MIN_POSITIVE) {
+        for (m, o) in cluster.members.iter().zip(&cluster.offsets) {
+            offsets[*m] = *o;
+        }
+    }
+    offsets
+}
@@ -0,0 +1,246 @@
+use std::fs;
+use std::io::{self, IsTerminal, Read};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+
+use week_01_multi_time_pad::corrections::Correction;
+use week_01_multi_time_pad::model;
+
+pub const USAGE: &str = "\
+usage: week_01-multi_time_pad [OPTIONS] [FILE...]
+
+Recover the key of ciphertexts encrypted with the same stream cipher key.
+Ciphertexts are read from the given files, or from stdin if there are none.
+
+options:
+  -e, --encoding <hex|base64|raw>  input encoding, hex and base64 inputs hold
+                                   one ciphertext per line, raw inputs are one
+                                   ciphertext per file (default: hex)
+  -t, --target <N>                 only print plaintext N (-1 is the last one)
+  -f, --format <text|json>         output format (default: text)
+  -k, --known <CT>:<OFFSET>:<TEXT> plaintext CT is known to read TEXT at OFFSET,
+                                   may be repeated
+  -K, --known-hex <CT>:<OFFSET>:<HEX>
+                                   same as --known with hex encoded bytes
+                                   (e.g. file magics), may be repeated
+  -c, --corrections <FILE>         plaintext corrections to load and, after a
+                                   crib session, save (default: corrections.txt)
+  -m, --model <MODEL>              plaintext model used for scoring, one of
+                                   english, bigram, printable, utf8, json,
+                                   hex, base64 (default: english)
+      --spaces                     use the space heuristic instead of scoring
+                                   (English text only)
+      --confidence                 highlight low-confidence plaintext bytes
+      --crib                       start an interactive crib-dragging session
+      --vigenere                   break each ciphertext as repeating-key xor
+      --detect                     find which ciphertexts of the input share a
+                                   keystream, then recover each group
+      --align                      the ciphertexts share one keystream but may
+                                   start at different positions in it, find
+                                   each one's offset before recovering the key
+      --max-shift <N>              with --detect or --align, largest keystream offset
+                                   between two ciphertexts (default: 32)
+      --threshold <N>              with --detect, link ciphertexts only when
+                                   independent keystreams would do as well
+                                   with a chance below 10^-N over the whole
+                                   input (default: 3)
+      --self-test                  check the library on known plaintexts
+  -h, --help                       print this help";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Hex,
+    Base64,
+    Raw,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+pub struct Options {
+    pub files: Vec<String>,
+    pub encoding: Encoding,
+    pub target: Option<isize>,
+    pub format: Format,
+    pub known: Vec<Correction>,
+    pub model: String,
+    pub corrections: String,
+    pub spaces: bool,
+    pub confidence: bool,
+    pub crib: bool,
+    pub vigenere: bool,
+    pub detect: bool,
+    pub align: bool,
+    pub max_shift: usize,
+    pub threshold: f64,
+    pub self_test: bool,
+    pub help: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            files: Vec::new(),
+            encoding: Encoding::Hex,
+            target: None,
+            format: Format::Text,
+            known: Vec::new(),
+            model: String::from("english"),
+            corrections: String::from("corrections.txt"),
+            spaces: false,
+            confidence: false,
+            crib: false,
+            vigenere: false,
+            detect: false,
+            align: false,
+            max_shift: 32,
+            threshold: 3.0,
+            self_test: false,
+            help: false,
+        }
+    }
+}
+
+// <ct>:<offset>:<text>, the text may itself contain ':'
+fn known(spec: &str, hex_text: bool) -> Result<Correction, String> {
+    let invalid = || format!("invalid known plaintext '{}', expected <ct>:<offset>:<text>", spec);
+    let mut fields = spec.splitn(3, ':');
+    let ct = fields.next().and_then(|x| x.parse().ok()).ok_or_else(invalid)?;
+    let offset = fields.next().and_then(|x| x.parse().ok()).ok_or_else(invalid)?;
+    let text = fields.next().filter(|t| !t.is_empty()).ok_or_else(invalid)?;
+    let text = if hex_text {
+        hex::decode(text).map_err(|e| format!("known plaintext '{}': {}", spec, e))?
+    } else {
+        text.as_bytes().to_vec()
+    };
+    Ok(Correction { ct, offset, text })
+}
+
+fn value<I: Iterator<Item = String>>(args: &mut I, opt: &str) -> Result<String, String> {
+    args.next().ok_or(format!("{} needs a value", opt))
+}
+
+// Parse the command line (without the program name)
+pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut opts = Options::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-e" | "--encoding" => {
+                opts.encoding = match value(&mut args, &arg)?.as_str() {
+                    "hex" => { Encoding::Hex }
+                    "base64" => { Encoding::Base64 }
+                    "raw" => { Encoding::Raw }
+                    x => { return Err(format!("unknown encoding '{}'", x)); }
+                };
+            }
+            "-t" | "--target" => {
+                let n = value(&mut args, &arg)?;
+                opts.target = Some(n.parse().map_err(|_| format!("invalid target '{}'", n))?);
+            }
+            "-f" | "--format" => {
+                opts.format = match value(&mut args, &arg)?.as_str() {
+                    "text" => { Format::Text }
+                    "json" => { Format::Json }
+                    x => { return Err(format!("unknown format '{}'", x)); }
+                };
+            }
+            "-k" | "--known" => { opts.known.push(known(&value(&mut args, &arg)?, false)?); }
+            "-K" | "--known-hex" => { opts.known.push(known(&value(&mut args, &arg)?, true)?); }
+            "-m" | "--model" => {
+                opts.model = value(&mut args, &arg)?;
+                if !model::MODELS.contains(&opts.model.as_str()) {
+                    return Err(format!("unknown model '{}', expected one of {}",
+                                       opts.model, model::MODELS.join(", ")));
+                }
+            }
+            "-c" | "--corrections" => { opts.corrections = value(&mut args, &arg)?; }
+            "--spaces" => { opts.spaces = true; }
+            "--confidence" => { opts.confidence = true; }
+            "--crib" => { opts.crib = true; }
+            "--vigenere" => { opts.vigenere = true; }
+            "--detect" => { opts.detect = true; }
+            "--align" => { opts.align = true; }
+            "--max-shift" => {
+                let n = value(&mut args, &arg)?;
+                opts.max_shift = n.parse().map_err(|_| format!("invalid shift '{}'", n))?;
+            }
+            "--threshold" => {
+                let n = value(&mut args, &arg)?;
+                opts.threshold = n.parse().map_err(|_| format!("invalid threshold '{}'", n))?;
+            }
+            "--self-test" => { opts.self_test = true; }
+            "-h" | "--help" => { opts.help = true; }
+            "-" => { opts.files.push(arg); }
+            x if x.starts_with('-') => { return Err(format!("unknown option '{}'", x)); }
+            _ => { opts.files.push(arg); }
+        }
+    }
+
+    if opts.crib && opts.files.iter().all(|f| f == "-") {
+        return Err(String::from("--crib reads commands from stdin, ciphertexts must come from files"));
+    }
+    Ok(opts)
+}
+
+fn decode(text: &str, encoding: Encoding) -> Result<Vec<u8>, String> {
+    match encoding {
+        Encoding::Hex => { hex::decode(text).map_err(|e| e.to_string()) }
+        Encoding::Base64 => { BASE64.decode(text).map_err(|e| e.to_string()) }
+        Encoding::Raw => { Ok(text.as_bytes().to_vec()) }
+    }
+}
+
+// Split the content of one input into ciphertexts
+fn parse_input(name: &str, content: Vec<u8>, encoding: Encoding) -> Result<Vec<Vec<u8>>, String> {
+    if encoding == Encoding::Raw {
+        return Ok(vec![content]);
+    }
+    let text = String::from_utf8(content).map_err(|_| format!("{}: not a text file", name))?;
+    text.lines().enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(n, line)| decode(line.trim(), encoding)
+             .map_err(|e| format!("{}:{}: {}", name, n + 1, e)))
+        .collect()
+}
+
+// Read all ciphertexts from the input files, or from stdin
+pub fn read_ciphertexts(opts: &Options) -> Result<Vec<Vec<u8>>, String> {
+    let mut ciphertexts: Vec<Vec<u8>> = Vec::new();
+
+    if opts.files.is_empty() {
+        if io::stdin().is_terminal() {
+            return Err(String::from("no input: give ciphertext files or pipe them on stdin"));
+        }
+        let mut content = Vec::new();
+        io::stdin().read_to_end(&mut content).map_err(|e| format!("stdin: {}", e))?;
+        ciphertexts.append(&mut parse_input("stdin", content, opts.encoding)?);
+    }
+    for name in opts.files.iter() {
+        let content = if name == "-" {
+            let mut content = Vec::new();
+            io::stdin().read_to_end(&mut content).map(|_| content)
+        } else {
+            fs::read(name)
+        }.map_err(|e| format!("{}: {}", name, e))?;
+        ciphertexts.append(&mut parse_input(name, content, opts.encoding)?);
+    }
+
+    if ciphertexts.is_empty() {
+        return Err(String::from("no ciphertexts in input"));
+    }
+    Ok(ciphertexts)
+}
+
+// Resolve --target against the number of ciphertexts (negative counts from the end)
+pub fn target_index(target: isize, count: usize) -> Result<usize, String> {
+    let idx = if target < 0 { count as isize + target } else { target };
+    if idx < 0 || idx as usize >= count {
+        return Err(format!("target {} out of range, there are {} ciphertexts", target, count));
+    }
+    Ok(idx as usize)
+}
@@ -0,0 +1,109 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::crib;
+
+// "plaintext <ct>, starting at <offset>, reads <text>"
+#[derive(Clone, Debug, PartialEq)]
+pub struct Correction {
+    pub ct: usize,
+    pub offset: usize,
+    pub text: Vec<u8>,
+}
+
+// One correction per line, same syntax as the crib session `commit`:
+//
+//   # comment
+//   10 7 r
+//   5 118 which will
+//
+// Everything after the second space up to the end of line is the text,
+// trailing spaces included. Bytes that cannot be written as is are
+// escaped, see escape.
+fn parse_line(line: &str) -> Option<Correction> {
+    let mut fields = line.splitn(3, ' ');
+    let ct = fields.next()?.parse().ok()?;
+    let offset = fields.next()?.parse().ok()?;
+    let text = unescape(fields.next().filter(|t| !t.is_empty())?);
+    Some(Correction { ct, offset, text })
+}
+
+// Text as saved: UTF-8 is kept as is, except for backslashes written as
+// \\ and control characters and invalid UTF-8 bytes written as \xHH,
+// so that any plaintext survives a save and load unchanged
+pub fn escape(text: &[u8]) -> String {
+    let mut out = String::new();
+    for chunk in text.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\\' => { out.push_str("\\\\"); }
+                _ if c.is_control() => {
+                    let mut buf = [0; 4];
+                    for b in c.encode_utf8(&mut buf).bytes() {
+                        out.push_str(&format!("\\x{:02x}", b));
+                    }
+                }
+                _ => { out.push(c); }
+            }
+        }
+        for b in chunk.invalid() {
+            out.push_str(&format!("\\x{:02x}", b));
+        }
+    }
+    out
+}
+
+// Inverse of escape. A backslash starting no valid escape is taken
+// literally, as files saved before escaping was introduced expect.
+pub fn unescape(text: &str) -> Vec<u8> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 2..i + 4).and_then(|h| hex::decode(h).ok()).map(|h| h[0]);
+        match (bytes[i], bytes.get(i + 1), hex) {
+            (b'\\', Some(b'\\'), _) => { out.push(b'\\'); i += 2; }
+            (b'\\', Some(b'x'), Some(b)) => { out.push(b); i += 4; }
+            (b, _, _) => { out.push(b); i += 1; }
+        }
+    }
+    out
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Vec<Correction>> {
+    let mut corrections: Vec<Correction> = Vec::new();
+    for (n, line) in fs::read_to_string(path)?.lines().enumerate() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_line(line) {
+            Some(c) => { corrections.push(c); }
+            None => {
+                return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                          format!("line {}: expected '<ct> <offset> <text>'", n + 1)));
+            }
+        }
+    }
+    Ok(corrections)
+}
+
+pub fn save<P: AsRef<Path>>(path: P, corrections: &[Correction]) -> io::Result<()> {
+    let mut out = String::from("# <ciphertext> <offset> <plaintext>\n");
+    for c in corrections {
+        out.push_str(&format!("{} {} {}\n", c.ct, c.offset, escape(&c.text)));
+    }
+    fs::write(path, out)
+}
+
+// Apply the corrections in order on top of a recovered key,
+// later ones win where they overlap.
+// Ciphertext c starts at keystream position offsets[c].
+pub fn apply(key: &mut [u8], ciphertexts: &[Vec<u8>], offsets: &[usize], corrections: &[Correction]) -> Result<(), String> {
+    for c in corrections {
+        let ct = ciphertexts.get(c.ct)
+                            .ok_or(format!("No ciphertext {} (only {})", c.ct, ciphertexts.len()))?;
+        crib::commit(&mut key[offsets[c.ct]..], ct, c.offset, &c.text)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,161 @@
+use std::io::{self, BufRead, Write};
+
+use crate::{english_score, ManyTimePad};
+use crate::corrections::Correction;
+
+// A place where dragging a crib over c_i ^ c_j gives printable text:
+// if m_i[offset..] is the crib, then m_j[j_offset..] is the counterpart
+// (the two offsets differ when the ciphertexts start at different
+// keystream positions)
+pub struct CribMatch {
+    pub i: usize,
+    pub j: usize,
+    pub offset: usize,
+    pub j_offset: usize,
+    pub counterpart: Vec<u8>,
+    pub score: f64,
+}
+
+fn is_printable(text: &[u8]) -> bool {
+    text.iter().all(|&b| b == b' ' || b.is_ascii_graphic())
+}
+
+// Slide the crib across every pairwise xor c_i ^ c_j (= m_i ^ m_j) of
+// bytes sharing a keystream position (ciphertext c starts at keystream
+// position offsets[c]): xoring the crib back at a given offset yields what
+// m_j would read there if m_i contained the crib. Keep only the printable
+// counterparts, best looking first.
+pub fn drag(ciphertexts: &[Vec<u8>], offsets: &[usize], crib: &[u8]) -> Vec<CribMatch> {
+    let mut matches: Vec<CribMatch> = Vec::new();
+
+    for (i, (ci, &oi)) in ciphertexts.iter().zip(offsets).enumerate() {
+        for (j, (cj, &oj)) in ciphertexts.iter().zip(offsets).enumerate() {
+            if i == j {
+                continue;
+            }
+            // keystream positions covered by both
+            let start = oi.max(oj);
+            let end = (oi + ci.len()).min(oj + cj.len());
+            if start + crib.len() > end {
+                continue;
+            }
+            for p in start..=(end - crib.len()) {
+                let (offset, j_offset) = (p - oi, p - oj);
+                let counterpart: Vec<u8> = crib.iter().enumerate()
+                    .map(|(n, b)| b ^ ci[offset + n] ^ cj[j_offset + n])
+                    .collect();
+                if is_printable(&counterpart) {
+                    let score = counterpart.iter().map(|&b| english_score(b)).sum();
+                    matches.push(CribMatch { i, j, offset, j_offset, counterpart, score });
+                }
+            }
+        }
+    }
+
+    matches.sort_by(|a, b| b.score.total_cmp(&a.score));
+    matches
+}
+
+// Fix m_ct[offset..] to the crib, updating the shared key accordingly
+pub fn commit(key: &mut [u8], ciphertext: &[u8], offset: usize, crib: &[u8]) -> Result<(), String> {
+    match offset.checked_add(crib.len()) {
+        Some(end) if end <= ciphertext.len() => {}
+        _ => {
+            return Err(format!("Crib does not fit in ciphertext ({} + {} > {})",
+                               offset, crib.len(), ciphertext.len()));
+        }
+    }
+    for (n, b) in crib.iter().enumerate() {
+        key[offset + n] = ciphertext[offset + n] ^ b;
+    }
+    Ok(())
+}
+
+// Printable version of a plaintext, unprintable bytes are shown as '.'
+pub fn render(plaintext: &[u8]) -> String {
+    plaintext.iter()
+        .map(|&b| if b == b' ' || b.is_ascii_graphic() { b as char } else { '.' })
+        .collect()
+}
+
+// Same as render, with the bytes under unrecovered key positions shown as '?'
+pub fn render_partial(plaintext: &[u8], recovered: &[bool]) -> String {
+    render(plaintext).chars().zip(recovered)
+                     .map(|(c, ok)| if *ok { c } else { '?' })
+                     .collect()
+}
+
+fn show(pad: &ManyTimePad) {
+    for (i, pt) in pad.decrypt_partial().iter().enumerate() {
+        let text: String = pt.iter()
+            .map(|b| match b {
+                Some(b) if *b == b' ' || b.is_ascii_graphic() => { *b as char }
+                Some(_) => { '.' }
+                None => { '?' }
+            })
+            .collect();
+        println!("{:3}: {}", i, text);
+    }
+}
+
+const HELP: &str = "\
+commands:
+  drag <crib>                  slide <crib> over all pairs of ciphertexts
+  commit <ct> <offset> <text>  plaintext <ct> at <offset> is <text>
+  show                         print all plaintexts
+  key                          print the current key
+  quit                         leave the session";
+
+// Interactive crib-dragging on top of an already recovered key.
+// Cribs may contain spaces, everything after the command (and its
+// numeric arguments) up to the end of line is taken verbatim.
+// Every committed crib becomes a known-plaintext constraint of the pad.
+pub fn session(pad: &mut ManyTimePad) {
+    println!("{}", HELP);
+    show(pad);
+
+    let stdin = io::stdin();
+    loop {
+        print!("crib> ");
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => { break; }
+            Ok(_) => {}
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        let (cmd, rest) = line.split_once(' ').unwrap_or((line, ""));
+
+        match cmd {
+            "drag" if !rest.is_empty() => {
+                let matches = drag(pad.ciphertexts(), pad.offsets(), rest.as_bytes());
+                for m in matches.iter().take(20) {
+                    println!("m{}[{}..] = {:?} => m{}[{}..] = {:?}",
+                             m.i, m.offset, rest, m.j, m.j_offset, render(&m.counterpart));
+                }
+                println!("{} printable positions", matches.len());
+            }
+            "commit" => {
+                let mut args = rest.splitn(3, ' ');
+                let ct = args.next().and_then(|x| x.parse::<usize>().ok());
+                let offset = args.next().and_then(|x| x.parse::<usize>().ok());
+                let text = args.next();
+                match (ct, offset, text) {
+                    (Some(ct), Some(offset), Some(text)) => {
+                        match pad.add_known(Correction { ct, offset, text: text.as_bytes().to_vec() }) {
+                            Ok(()) => { show(pad); }
+                            Err(e) => { println!("{}", e); }
+                        }
+                    }
+                    _ => { println!("usage: commit <ct> <offset> <text>"); }
+                }
+            }
+            "show" => { show(pad); }
+            "key" => { println!("{}", hex::encode(pad.key())); }
+            "quit" | "exit" => { break; }
+            "" => {}
+            _ => { println!("{}", HELP); }
+        }
+    }
+}
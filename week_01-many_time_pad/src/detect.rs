@@ -0,0 +1,196 @@
+// Keystream reuse detection over a corpus of ciphertexts.
+//
+// If c_i and c_j were encrypted with the same keystream, with c_j
+// starting `shift` bytes further in it than c_i, then for every
+// overlapping position c_i[k + shift] ^ c_j[k] = m_i[k + shift] ^ m_j[k]:
+// the xor of two plaintexts, which is far from uniform. With independent
+// keystreams the xor is uniformly random. Two statistics tell them apart:
+// - how often the xor is zero (same plaintext byte on both sides), 1/256
+//   of the time if random, several percent for text
+// - how often the xor has its top bit clear, half of the time if random,
+//   always for ASCII plaintexts
+// Both counts are binomial with independent keystreams, so the chance of
+// seeing as many by luck is known exactly. Since the best of many shifts
+// and pairs is kept, that chance is multiplied by how many were tried
+// before anything is linked (Bonferroni correction).
+
+use std::f64::consts::PI;
+
+// Best alignment found between two ciphertexts
+#[derive(Clone, Debug)]
+pub struct PairStat {
+    pub i: usize,
+    pub j: usize,
+    // keystream offset of c_j minus keystream offset of c_i
+    pub shift: isize,
+    pub overlap: usize,
+    // how strongly the pair looks like sharing a keystream at that shift,
+    // for pair_score -log10 of the chance of doing as well with independent
+    // keystreams
+    pub score: f64,
+}
+
+// Ciphertexts sharing one keystream, each with its offset in it
+#[derive(Clone, Debug)]
+pub struct Cluster {
+    pub members: Vec<usize>,
+    pub offsets: Vec<usize>,
+}
+
+// Xor of the overlap of c_i and c_j when c_j starts shift bytes later
+// in the keystream than c_i (earlier if shift is negative)
+pub fn overlap_xor(ci: &[u8], cj: &[u8], shift: isize) -> Vec<u8> {
+    ci.iter().enumerate()
+        .filter_map(|(k, a)| {
+            let kj = k as isize - shift;
+            (kj >= 0).then(|| cj.get(kj as usize).map(|b| a ^ b)).flatten()
+        })
+        .collect()
+}
+
+// ln n!, exact for small n, Stirling's series (error below 1e-12) above
+fn ln_factorial(n: usize) -> f64 {
+    if n < 20 {
+        return (2..=n).map(|i| (i as f64).ln()).sum();
+    }
+    let n = n as f64;
+    n * n.ln() - n + 0.5 * (2.0 * PI * n).ln() + 1.0 / (12.0 * n) - 1.0 / (360.0 * n.powi(3))
+}
+
+// ln P(X >= k) for X ~ Binomial(n, p), taken as 0 (certain) up to the
+// mean since only an excess over chance matters here
+fn ln_binomial_tail(n: usize, k: usize, p: f64) -> f64 {
+    if k as f64 <= n as f64 * p {
+        return 0.0;
+    }
+    let ln_pmf = ln_factorial(n) - ln_factorial(k) - ln_factorial(n - k)
+               + k as f64 * p.ln() + (n - k) as f64 * (1.0 - p).ln();
+    // P(X = i + 1) / P(X = i) only decreases past the mean
+    let (mut term, mut sum) = (1.0, 1.0);
+    for i in k..n {
+        term *= (n - i) as f64 / (i + 1) as f64 * p / (1.0 - p);
+        sum += term;
+        if term < sum * 1e-16 {
+            break;
+        }
+    }
+    (ln_pmf + sum.ln()).min(0.0)
+}
+
+// Score the overlap of c_i and c_j at the given shift: -log10 of the
+// chance that independent keystreams give at least as many zero bytes or
+// at least as many bytes with their top bit clear (twice the smaller of
+// the two tails, as two statistics are looked at)
+pub fn pair_score(ci: &[u8], cj: &[u8], shift: isize) -> (usize, f64) {
+    let xor = overlap_xor(ci, cj, shift);
+    let n = xor.len();
+    if xor.is_empty() {
+        return (0, 0.0);
+    }
+
+    let zeros = xor.iter().filter(|&&x| x == 0).count();
+    let low = xor.iter().filter(|&&x| x < 0x80).count();
+    let ln_p = ln_binomial_tail(n, zeros, 1.0 / 256.0).min(ln_binomial_tail(n, low, 0.5)) + 2.0f64.ln();
+
+    (n, -ln_p.min(0.0) / 10.0f64.ln())
+}
+
+// Minimum pair_score for the chance that independent keystreams link any
+// two of count ciphertexts, at any shift within +/- max_shift, to be at
+// most 10^-significance
+pub fn threshold(count: usize, max_shift: usize, significance: f64) -> f64 {
+    let pairs = count * count.saturating_sub(1) / 2;
+    let tests = pairs.max(1) * (2 * max_shift + 1);
+    significance + (tests as f64).log10()
+}
+
+// For every pair of ciphertexts, the best shift within +/- max_shift
+// having at least min_overlap overlapping bytes
+pub fn pair_stats(ciphertexts: &[Vec<u8>], max_shift: usize, min_overlap: usize) -> Vec<PairStat> {
+    pair_stats_by(ciphertexts, max_shift, min_overlap, pair_score)
+}
+
+// Same as pair_stats, with score(c_i, c_j, shift) -> (overlap, score)
+pub fn pair_stats_by<F>(ciphertexts: &[Vec<u8>], max_shift: usize, min_overlap: usize, score: F) -> Vec<PairStat>
+    where F: Fn(&[u8], &[u8], isize) -> (usize, f64) {
+    let mut stats: Vec<PairStat> = Vec::new();
+
+    for i in 0..ciphertexts.len() {
+        for j in (i + 1)..ciphertexts.len() {
+            let best = (-(max_shift as isize)..=max_shift as isize)
+                .map(|shift| (shift, score(&ciphertexts[i], &ciphertexts[j], shift)))
+                .filter(|(_, (overlap, _))| *overlap >= min_overlap)
+                .max_by(|a, b| a.1.1.total_cmp(&b.1.1));
+            if let Some((shift, (overlap, score))) = best {
+                stats.push(PairStat { i, j, shift, overlap, score });
+            }
+        }
+    }
+    stats
+}
+
+// Union-find keeping, for every node, its keystream offset
+// relative to its parent
+struct Offsets {
+    parent: Vec<usize>,
+    offset: Vec<isize>,
+}
+
+impl Offsets {
+    fn new(n: usize) -> Self {
+        Self { parent: (0..n).collect(), offset: vec![0; n] }
+    }
+
+    // (root, offset of x relative to root)
+    fn find(&mut self, x: usize) -> (usize, isize) {
+        if self.parent[x] == x {
+            return (x, 0);
+        }
+        let (root, parent_offset) = self.find(self.parent[x]);
+        self.parent[x] = root;
+        self.offset[x] += parent_offset;
+        (root, self.offset[x])
+    }
+
+    // offset(j) - offset(i) = shift
+    fn union(&mut self, i: usize, j: usize, shift: isize) {
+        let (ri, oi) = self.find(i);
+        let (rj, oj) = self.find(j);
+        if ri != rj {
+            self.parent[rj] = ri;
+            self.offset[rj] = oi + shift - oj;
+        }
+    }
+}
+
+// Group ciphertexts linked by pairs scoring at least threshold.
+// Strongest pairs are linked first, a weaker pair contradicting the
+// offsets already implied by stronger ones is ignored. Offsets are
+// normalized so the earliest member of each cluster starts at 0.
+// Ciphertexts sharing their keystream with nobody are left out.
+pub fn clusters(count: usize, stats: &[PairStat], threshold: f64) -> Vec<Cluster> {
+    let mut strong: Vec<&PairStat> = stats.iter().filter(|s| s.score >= threshold).collect();
+    strong.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    let mut sets = Offsets::new(count);
+    for s in strong {
+        sets.union(s.i, s.j, s.shift);
+    }
+
+    let mut clusters: Vec<(usize, Vec<usize>, Vec<isize>)> = Vec::new();
+    for x in 0..count {
+        let (root, offset) = sets.find(x);
+        match clusters.iter_mut().find(|c| c.0 == root) {
+            Some(c) => { c.1.push(x); c.2.push(offset); }
+            None => { clusters.push((root, vec![x], vec![offset])); }
+        }
+    }
+
+    clusters.into_iter()
+        .filter(|c| c.1.len() > 1)
+        .map(|(_, members, offsets)| {
+            let min = *offsets.iter().min().unwrap();
+            Cluster { members, offsets: offsets.iter().map(|o| (o - min) as usize).collect() }
+        })
+        .collect()
+}
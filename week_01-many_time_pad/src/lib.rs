@@ -0,0 +1,275 @@
+pub mod align;
+pub mod corrections;
+pub mod crib;
+pub mod detect;
+pub mod model;
+pub mod report;
+pub mod vigenere;
+
+use corrections::Correction;
+pub use model::{english_score, PlaintextModel};
+
+// The space heuristic only makes sense on English (ASCII) text,
+// scoring works with any plaintext model
+pub enum Recovery<'a> {
+    SpaceHeuristic,
+    Scoring(&'a dyn PlaintextModel),
+}
+
+// Ciphertext byte encrypted with key[i], for a ciphertext starting at
+// keystream position offset
+fn at(ct: &[u8], offset: usize, i: usize) -> Option<u8> {
+    i.checked_sub(offset).and_then(|k| ct.get(k).copied())
+}
+
+fn find_key(votes: &mut [[u32; 256]], (c1, o1): (&[u8], usize), (c2, o2): (&[u8], usize), (c3, o3): (&[u8], usize)) {
+    // Idea:
+    // - construct c1 xor c2, c1 xor c3, c2 xor c3
+    // - if at position i, e.g. (c1 xor c2) and (c1 xor c3) is a valid ACII
+    //   then we infer that m1[i] is a space
+    // - then c1[i] xor ' ' gets a vote as key[i]
+
+    let start = *[o1, o2, o3].iter().max().unwrap();
+    let end = *[o1 + c1.len(), o2 + c2.len(), o3 + c3.len()].iter().min().unwrap();
+
+    assert!(votes.len() >= end);
+
+    for i in start..end {
+        let (b1, b2, b3) = (c1[i - o1], c2[i - o2], c3[i - o3]);
+        let c12 = (b1 ^ b2).is_ascii_alphabetic();
+        let c13 = (b1 ^ b3).is_ascii_alphabetic();
+        let c23 = (b2 ^ b3).is_ascii_alphabetic();
+        if c12 && c13 {
+            votes[i][(b1 ^ b' ') as usize] += 1;
+        } else if c12 && c23 {
+            votes[i][(b2 ^ b' ') as usize] += 1;
+        } else if c23 && c13 {
+            votes[i][(b3 ^ b' ') as usize] += 1;
+        }
+    }
+}
+
+// Pick, for key position i, the byte k maximizing the model score of
+// c[i] ^ k summed over every ciphertext c reaching i, given the plaintext
+// byte before it as decrypted with the key picked so far.
+// Ciphertext c starts at keystream position offsets[c].
+// Scores are log-likelihoods, so the confidence of the pick is its
+// posterior probability among all 256 candidates.
+pub fn score_key(key: &mut [u8], confidence: &mut [f64], ciphertexts: &[Vec<u8>], offsets: &[usize], model: &dyn PlaintextModel) {
+    for i in 0..key.len() {
+        let column: Vec<(Option<u8>, u8)> = ciphertexts.iter().zip(offsets)
+            .filter_map(|(ct, &o)| {
+                let prev = if i > o { at(ct, o, i - 1).map(|c| c ^ key[i - 1]) } else { None };
+                at(ct, o, i).map(|c| (prev, c))
+            })
+            .collect();
+        if column.is_empty() {
+            continue;
+        }
+        let scores: Vec<f64> = (0..=255u8)
+            .map(|guess| column.iter().map(|(prev, c)| model.score_after(*prev, c ^ guess)).sum())
+            .collect();
+        let (best, best_score) = scores.iter().enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .unwrap();
+        key[i] = best as u8;
+        confidence[i] = 1.0 / scores.iter().map(|s| (s - best_score).exp()).sum::<f64>();
+    }
+}
+
+// When only two ciphertexts reach a position there is no third one to
+// tell which of the two plaintexts holds the space: vote for both
+fn find_key_pair(votes: &mut [[u32; 256]], coverage: &[usize], (c1, o1): (&[u8], usize), (c2, o2): (&[u8], usize)) {
+    let start = o1.max(o2);
+    let end = (o1 + c1.len()).min(o2 + c2.len());
+
+    for i in start..end {
+        let (b1, b2) = (c1[i - o1], c2[i - o2]);
+        if coverage[i] == 2 && (b1 ^ b2).is_ascii_alphabetic() {
+            votes[i][(b1 ^ b' ') as usize] += 1;
+            votes[i][(b2 ^ b' ') as usize] += 1;
+        }
+    }
+}
+
+// Length of the keystream needed by ciphertexts starting at offsets
+pub fn key_len(ciphertexts: &[Vec<u8>], offsets: &[usize]) -> usize {
+    ciphertexts.iter().zip(offsets).map(|(ct, o)| o + ct.len()).max().unwrap_or(0)
+}
+
+// How many ciphertexts reach each of the first len key positions
+pub fn coverage(ciphertexts: &[Vec<u8>], offsets: &[usize], len: usize) -> Vec<usize> {
+    (0..len).map(|i| ciphertexts.iter().zip(offsets).filter(|(ct, &o)| at(ct, o, i).is_some()).count())
+            .collect()
+}
+
+// Recover as much of the key as possible, returning for every position
+// how confident we are about it (0.0 = no evidence at all, 1.0 = certain).
+// Ciphertext c starts at keystream position offsets[c].
+// A position reached by a single ciphertext cannot be recovered (any key
+// byte gives an equally valid plaintext), so it is zeroed with no
+// confidence, as is any other position without evidence.
+pub fn recover_key(key: &mut [u8], ciphertexts: &[Vec<u8>], offsets: &[usize], mode: Recovery) -> Vec<f64> {
+    let mut confidence = vec![0.0; key.len()];
+    let coverage = coverage(ciphertexts, offsets, key.len());
+    let cts: Vec<(&[u8], usize)> = ciphertexts.iter().map(|ct| ct.as_slice()).zip(offsets.iter().copied()).collect();
+    match mode {
+        Recovery::SpaceHeuristic => {
+            // every triple votes, the most voted byte wins;
+            // pairs only vote where there are no triples
+            let mut votes = vec![[0u32; 256]; key.len()];
+            for i in 0..cts.len() {
+                for j in (i + 1)..cts.len() {
+                    find_key_pair(&mut votes, &coverage, cts[i], cts[j]);
+                    for k in (j+1)..cts.len() {
+                        find_key(&mut votes, cts[i], cts[j], cts[k]);
+                    }
+                }
+            }
+            for (i, v) in votes.iter().enumerate() {
+                let total: u32 = v.iter().sum();
+                if total == 0 {
+                    key[i] = 0;
+                    continue;
+                }
+                let (best, count) = v.iter().enumerate().max_by_key(|x| x.1).unwrap();
+                key[i] = best as u8;
+                confidence[i] = *count as f64 / total as f64;
+            }
+        }
+        Recovery::Scoring(model) => { score_key(key, &mut confidence, ciphertexts, offsets, model) }
+    }
+    for (i, c) in coverage.iter().enumerate() {
+        if *c < 2 {
+            key[i] = 0;
+            confidence[i] = 0.0;
+        }
+    }
+    confidence
+}
+
+pub fn decrypt(key: &[u8], ciphertexts: &[Vec<u8>], offsets: &[usize]) -> Vec<Vec<u8>>
+{
+    let mut plaintexts: Vec<Vec<u8>> = Vec::new();
+
+    for (i, (ct, o)) in ciphertexts.iter().zip(offsets).enumerate() {
+        plaintexts.push(Vec::new());
+        for j in 0..ct.len() {
+            plaintexts[i].push(key[o + j] ^ ct[j]);
+        }
+    }
+
+    plaintexts
+}
+
+// A set of ciphertexts encrypted under the same keystream, each starting
+// at its own offset in it, together with what we know so far about that
+// keystream: the (partial) key, how many ciphertexts reach and how much we
+// trust each of its bytes and the known-plaintext constraints, which
+// always take precedence over whatever the heuristics recover
+pub struct ManyTimePad {
+    ciphertexts: Vec<Vec<u8>>,
+    offsets: Vec<usize>,
+    coverage: Vec<usize>,
+    key: Vec<u8>,
+    confidence: Vec<f64>,
+    known: Vec<Correction>,
+}
+
+impl ManyTimePad {
+    // All ciphertexts start at the beginning of the keystream
+    pub fn new(ciphertexts: Vec<Vec<u8>>) -> Self {
+        let offsets = vec![0; ciphertexts.len()];
+        Self::with_offsets(ciphertexts, offsets)
+    }
+
+    // Ciphertext i starts at keystream position offsets[i]
+    pub fn with_offsets(ciphertexts: Vec<Vec<u8>>, offsets: Vec<usize>) -> Self {
+        assert_eq!(ciphertexts.len(), offsets.len());
+        let len = key_len(&ciphertexts, &offsets);
+        Self {
+            coverage: coverage(&ciphertexts, &offsets, len),
+            ciphertexts,
+            offsets,
+            key: vec![0; len],
+            confidence: vec![0.0; len],
+            known: Vec::new(),
+        }
+    }
+
+    pub fn ciphertexts(&self) -> &[Vec<u8>] {
+        &self.ciphertexts
+    }
+
+    pub fn offsets(&self) -> &[usize] {
+        &self.offsets
+    }
+
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    // 0.0 = no evidence at all, 1.0 = certain (e.g. known plaintext)
+    pub fn confidence(&self) -> &[f64] {
+        &self.confidence
+    }
+
+    pub fn coverage(&self) -> &[usize] {
+        &self.coverage
+    }
+
+    // Key positions with any evidence: known plaintext, or enough
+    // ciphertexts for the heuristics to say something
+    pub fn recovered(&self) -> Vec<bool> {
+        self.confidence.iter().map(|&c| c > 0.0).collect()
+    }
+
+    pub fn known(&self) -> &[Correction] {
+        &self.known
+    }
+
+    // Run the heuristics over the whole key, then re-apply the constraints
+    pub fn recover_key(&mut self, mode: Recovery) {
+        self.confidence = recover_key(&mut self.key, &self.ciphertexts, &self.offsets, mode);
+        self.apply_known();
+    }
+
+    // Constrain plaintext c.ct at c.offset to c.text, fixing the key there
+    pub fn add_known(&mut self, c: Correction) -> Result<(), String> {
+        let start = match self.offsets.get(c.ct) {
+            Some(o) => { o.checked_add(c.offset).ok_or(format!("Offset {} out of range", c.offset))? }
+            None => { return Err(format!("No ciphertext {} (only {})", c.ct, self.ciphertexts.len())); }
+        };
+        corrections::apply(&mut self.key, &self.ciphertexts, &self.offsets, std::slice::from_ref(&c))?;
+        self.confidence[start..start + c.text.len()].fill(1.0);
+        self.known.push(c);
+        Ok(())
+    }
+
+    fn apply_known(&mut self) {
+        // already validated when added
+        corrections::apply(&mut self.key, &self.ciphertexts, &self.offsets, &self.known).unwrap();
+        for c in self.known.iter() {
+            let start = self.offsets[c.ct] + c.offset;
+            self.confidence[start..start + c.text.len()].fill(1.0);
+        }
+    }
+
+    pub fn decrypt(&self) -> Vec<Vec<u8>> {
+        decrypt(&self.key, &self.ciphertexts, &self.offsets)
+    }
+
+    // Same as decrypt, with None where the key byte was not recovered
+    pub fn decrypt_partial(&self) -> Vec<Vec<Option<u8>>> {
+        let recovered = self.recovered();
+        self.decrypt().iter().zip(&self.offsets)
+            .map(|(pt, &o)| pt.iter().zip(&recovered[o..]).map(|(b, ok)| ok.then_some(*b)).collect())
+            .collect()
+    }
+
+    // Confidence of the key bytes under plaintext i
+    pub fn plaintext_confidence(&self, i: usize) -> &[f64] {
+        let o = self.offsets[i];
+        &self.confidence[o..o + self.ciphertexts[i].len()]
+    }
+}
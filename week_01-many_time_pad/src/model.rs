@@ -0,0 +1,231 @@
+// Plaintext language models used to score candidate key bytes.
+// A model gives the log-likelihood of a plaintext byte, optionally
+// knowing the byte before it in the same plaintext.
+
+// Relative frequency (in percent) of the letters 'a' ... 'z' in English text
+const ENGLISH_FREQ: [f64; 26] = [
+    8.167, 1.492, 2.782, 4.253, 12.702, 2.228, 2.015, 6.094, 6.966, 0.153, 0.772, 4.025, 2.406,
+    6.749, 7.507, 1.929, 0.095, 5.987, 6.327, 9.056, 2.758, 0.978, 2.360, 0.150, 1.974, 0.074,
+];
+
+// Probability of a byte a model does not expect at all
+const UNEXPECTED: f64 = 0.000001;
+
+// Sample prose the default bigram model is trained on: the openings of
+// public domain works (Dickens, Austen, Melville, Carroll, Conan Doyle,
+// C. Bronte) and of the Declaration of Independence and the Gettysburg
+// Address, about 1700 words
+const ENGLISH_SAMPLE: &str = include_str!("../data/english.txt");
+
+pub trait PlaintextModel {
+    // Log-likelihood of the plaintext byte b
+    fn score(&self, b: u8) -> f64;
+
+    // Log-likelihood of b following prev in the same plaintext
+    // (None at the start of a plaintext), context-free by default
+    fn score_after(&self, prev: Option<u8>, b: u8) -> f64 {
+        let _ = prev;
+        self.score(b)
+    }
+}
+
+// How much a single plaintext byte looks like English text, as the log of
+// its (rough) probability: lowercase letters weigh by their frequency, a
+// space is the most likely character, capitals, punctuation and digits are
+// plausible but rarer, anything else is heavily penalized
+pub fn english_score(b: u8) -> f64 {
+    let p = match b {
+        b' ' => 18.0,
+        b'a'..=b'z' => ENGLISH_FREQ[(b - b'a') as usize] * 0.75,
+        b'A'..=b'Z' => ENGLISH_FREQ[(b - b'A') as usize] * 0.25,
+        b'.' | b',' | b'\'' => 0.8,
+        b'0'..=b'9' | b'!' | b'?' | b'-' | b':' | b';' | b'"' | b'(' | b')' => 0.1,
+        0x21..=0x7e => 0.005,
+        _ => UNEXPECTED,
+    };
+    p.ln()
+}
+
+// English text, letter by letter
+pub struct English;
+
+impl PlaintextModel for English {
+    fn score(&self, b: u8) -> f64 {
+        english_score(b)
+    }
+}
+
+// Bigram classes: 'a' ... 'z' (case folded) and space
+const CLASSES: usize = 27;
+
+fn class(b: u8) -> Option<usize> {
+    match b {
+        b'a'..=b'z' => Some((b - b'a') as usize),
+        b'A'..=b'Z' => Some((b - b'A') as usize),
+        b' ' => Some(26),
+        _ => None,
+    }
+}
+
+// English text, letter pairs: the letter score corrected by how much more
+// (or less) likely the letter is right after the previous one,
+// i.e. log P(b) + log P(b | prev) - log P(b) on letters and spaces
+pub struct Bigram {
+    // log P(b | a) - log P(b) for classes a, b
+    lift: [[f64; CLASSES]; CLASSES],
+}
+
+impl Bigram {
+    // Train on a sample text, with add-one smoothing
+    pub fn from_corpus(corpus: &str) -> Self {
+        let mut pairs = [[1.0f64; CLASSES]; CLASSES];
+        let mut singles = [CLASSES as f64; CLASSES];
+        // line breaks and runs of spaces separate words like a single space
+        let words = corpus.split_whitespace().collect::<Vec<_>>().join(" ");
+        let classes: Vec<usize> = words.bytes().filter_map(class).collect();
+        for w in classes.windows(2) {
+            pairs[w[0]][w[1]] += 1.0;
+            singles[w[1]] += 1.0;
+        }
+        let total: f64 = singles.iter().sum();
+
+        let mut lift = [[0.0; CLASSES]; CLASSES];
+        for a in 0..CLASSES {
+            let row: f64 = pairs[a].iter().sum();
+            for b in 0..CLASSES {
+                lift[a][b] = (pairs[a][b] / row).ln() - (singles[b] / total).ln();
+            }
+        }
+        Self { lift }
+    }
+}
+
+impl Default for Bigram {
+    fn default() -> Self {
+        Self::from_corpus(ENGLISH_SAMPLE)
+    }
+}
+
+impl PlaintextModel for Bigram {
+    fn score(&self, b: u8) -> f64 {
+        english_score(b)
+    }
+
+    fn score_after(&self, prev: Option<u8>, b: u8) -> f64 {
+        match (prev.and_then(class), class(b)) {
+            (Some(a), Some(c)) => { english_score(b) + self.lift[a][c] }
+            _ => { english_score(b) }
+        }
+    }
+}
+
+// Any byte of a given alphabet, with relative weights
+pub struct Alphabet {
+    scores: [f64; 256],
+}
+
+impl Alphabet {
+    // weights[b] is the relative weight of byte b, 0 if not in the alphabet
+    pub fn new(weights: &[f64; 256]) -> Self {
+        let total: f64 = weights.iter().sum();
+        let mut scores = [UNEXPECTED.ln(); 256];
+        for (s, w) in scores.iter_mut().zip(weights) {
+            if *w > 0.0 {
+                *s = (w / total).ln();
+            }
+        }
+        Self { scores }
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let mut weights = [0.0; 256];
+        for &b in bytes {
+            weights[b as usize] = 1.0;
+        }
+        Self::new(&weights)
+    }
+
+    // Printable ASCII and usual whitespace, all equally likely
+    pub fn printable() -> Self {
+        let bytes: Vec<u8> = (0x20..0x7f).chain([b'\t', b'\n', b'\r']).collect();
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn hex() -> Self {
+        Self::from_bytes(b"0123456789abcdefABCDEF")
+    }
+
+    pub fn base64() -> Self {
+        Self::from_bytes(b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/=")
+    }
+
+    // Printable ASCII, heavy on the JSON punctuation, quotes above all
+    pub fn json() -> Self {
+        let mut weights = [0.0; 256];
+        for b in (0x20..0x7f).chain([b'\n', b'\t']) {
+            weights[b as usize] = 1.0;
+        }
+        for b in b"{}[]:,".iter() {
+            weights[*b as usize] = 8.0;
+        }
+        for b in b"abcdefghijklmnopqrstuvwxyz0123456789 ".iter() {
+            weights[*b as usize] = 3.0;
+        }
+        weights[b'"' as usize] = 20.0;
+        Self::new(&weights)
+    }
+}
+
+impl PlaintextModel for Alphabet {
+    fn score(&self, b: u8) -> f64 {
+        self.scores[b as usize]
+    }
+}
+
+// UTF-8 encoded text: ASCII scored as English, multi-byte sequences are
+// plausible as long as lead and continuation bytes follow each other
+pub struct Utf8;
+
+fn is_lead(b: u8) -> bool {
+    (0xc2..=0xf4).contains(&b)
+}
+
+fn is_continuation(b: u8) -> bool {
+    (0x80..=0xbf).contains(&b)
+}
+
+impl PlaintextModel for Utf8 {
+    fn score(&self, b: u8) -> f64 {
+        self.score_after(None, b)
+    }
+
+    fn score_after(&self, prev: Option<u8>, b: u8) -> f64 {
+        let after_lead = prev.is_some_and(is_lead);
+        let after_continuation = prev.is_some_and(is_continuation);
+        match b {
+            _ if after_lead && is_continuation(b) => { 0.0 }
+            _ if after_lead => { UNEXPECTED.ln() }
+            0x00..=0x7f => { english_score(b) }
+            _ if is_lead(b) => { 0.02f64.ln() }
+            _ if is_continuation(b) && after_continuation => { 0.3f64.ln() }
+            _ => { UNEXPECTED.ln() }
+        }
+    }
+}
+
+// Names accepted by by_name
+pub const MODELS: &[&str] = &["english", "bigram", "printable", "utf8", "json", "hex", "base64"];
+
+pub fn by_name(name: &str) -> Option<Box<dyn PlaintextModel>> {
+    let model: Box<dyn PlaintextModel> = match name {
+        "english" => { Box::new(English) }
+        "bigram" => { Box::new(Bigram::default()) }
+        "printable" => { Box::new(Alphabet::printable()) }
+        "utf8" => { Box::new(Utf8) }
+        "json" => { Box::new(Alphabet::json()) }
+        "hex" => { Box::new(Alphabet::hex()) }
+        "base64" => { Box::new(Alphabet::base64()) }
+        _ => { return None; }
+    };
+    Some(model)
+}
@@ -0,0 +1,38 @@
+use crate::crib::render_partial;
+
+// below LOW a key byte is likely wrong, below HIGH it deserves a look
+pub const LOW: f64 = 0.5;
+pub const HIGH: f64 = 0.9;
+
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+// Plaintext with every byte coloured by the confidence of its key byte:
+// red when low, yellow when medium, default colour when high.
+// Bytes without any evidence are a red '?'.
+pub fn colour(plaintext: &[u8], confidence: &[f64]) -> String {
+    let recovered: Vec<bool> = confidence.iter().map(|&c| c > 0.0).collect();
+    let mut out = String::new();
+    for (c, conf) in render_partial(plaintext, &recovered).chars().zip(confidence) {
+        if *conf < LOW {
+            out.push_str(RED);
+        } else if *conf < HIGH {
+            out.push_str(YELLOW);
+        } else {
+            out.push(c);
+            continue;
+        }
+        out.push(c);
+        out.push_str(RESET);
+    }
+    out
+}
+
+// Key positions below the LOW threshold within the first len bytes
+pub fn low_confidence(confidence: &[f64], len: usize) -> Vec<usize> {
+    confidence.iter().take(len).enumerate()
+              .filter(|(_, c)| **c < LOW)
+              .map(|(i, _)| i)
+              .collect()
+}
@@ -0,0 +1,166 @@
+// Known-plaintext checks of the library, run with --self-test:
+// English messages encrypted under one random keystream must come back.
+
+use week_01_multi_time_pad::corrections::{self, Correction};
+use week_01_multi_time_pad::model::{Bigram, English};
+use week_01_multi_time_pad::PlaintextModel;
+use week_01_multi_time_pad::{coverage, crib, detect, report, vigenere, ManyTimePad, Recovery};
+
+const MESSAGES: &[&str] = &[
+    "The secret message is: When using a stream cipher, never use the key more than once",
+    "We can factor the number 15 with quantum computers. We can also factor the number 1",
+    "Euler would probably enjoy that now his theorem becomes a corner stone of crypto - ",
+    "The nice thing about Keeyloq is now we cryptographers can drive a lot of fancy cars",
+    "The ciphertext produced by a weak encryption algorithm looks as good as ciphertext ",
+    "You don't want to buy a set of car keys from a guy who specializes in stealing cars",
+    "There are two types of cryptography - that which will keep secrets safe from your l",
+    "There are two types of cyptography: one that allows the Government to use brute for",
+    "We can see the point where the chip is unhappy if a wrong bit is sent and consumes ",
+    "A (private-key)  encryption scheme states 3 algorithms, namely a procedure for gene",
+    "An attacker who knows that a pad is used more than once can read every message sent",
+];
+
+// xorshift64*, deterministic so that a failure can be reproduced
+struct Rng(u64);
+
+impl Rng {
+    fn byte(&mut self) -> u8 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545f4914f6cdd1d) >> 56) as u8
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.byte()).collect()
+    }
+}
+
+fn encrypt(keystream: &[u8], plaintexts: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    plaintexts.iter().map(|pt| pt.iter().zip(keystream).map(|(m, k)| m ^ k).collect()).collect()
+}
+
+// Fraction of equal bytes in the first n
+fn matching(a: &[u8], b: &[u8], n: usize) -> f64 {
+    a.iter().zip(b).take(n).filter(|(x, y)| x == y).count() as f64 / n as f64
+}
+
+pub fn run() {
+    let mut rng = Rng(0x5eed);
+    let len = MESSAGES.iter().map(|m| m.len()).min().unwrap();
+    let plaintexts: Vec<Vec<u8>> = MESSAGES.iter().map(|m| m.as_bytes()[..len].to_vec()).collect();
+    let keystream = rng.bytes(128);
+
+    // every position is covered by all the ciphertexts: scoring and the
+    // space heuristic both get nearly all of the key right
+    let bigram = Bigram::default();
+    for (mode, expected) in [(Recovery::Scoring(&English), 0.95), (Recovery::Scoring(&bigram), 0.95),
+                             (Recovery::SpaceHeuristic, 0.8)] {
+        let mut pad = ManyTimePad::new(encrypt(&keystream, &plaintexts));
+        pad.recover_key(mode);
+        assert!(matching(pad.key(), &keystream, len) >= expected);
+        let right: f64 = pad.decrypt().iter().zip(&plaintexts).map(|(d, p)| matching(d, p, p.len())).sum();
+        assert!(right / plaintexts.len() as f64 >= expected);
+    }
+
+    // the bigram model knows which letters follow which
+    for (prev, b) in [(b't', b'h'), (b'q', b'u'), (b'n', b'g'), (b'e', b' '), (b' ', b't')] {
+        assert!(bigram.score_after(Some(prev), b) > bigram.score(b));
+    }
+    for (prev, b) in [(b'q', b'e'), (b'j', b't'), (b'v', b't'), (b' ', b' ')] {
+        assert!(bigram.score_after(Some(prev), b) < bigram.score(b));
+    }
+
+    // past the end of all but one ciphertext, nothing can be recovered
+    let mut long = plaintexts.clone();
+    long[3].extend_from_slice(b" and keep on driving them all over the place");
+    let mut pad = ManyTimePad::new(encrypt(&keystream, &long));
+    assert_eq!(coverage(pad.ciphertexts(), pad.offsets(), pad.key().len()), pad.coverage());
+    assert!(pad.coverage()[..len].iter().all(|&c| c == long.len()));
+    assert!(pad.coverage()[len..].iter().all(|&c| c == 1));
+    pad.recover_key(Recovery::Scoring(&English));
+    assert!(pad.key()[len..].iter().all(|&k| k == 0));
+    assert!(pad.confidence()[len..].iter().all(|&c| c == 0.0));
+    let low = report::low_confidence(pad.plaintext_confidence(3), long[3].len());
+    assert!((len..long[3].len()).all(|n| low.contains(&n)));
+    assert!(report::low_confidence(pad.plaintext_confidence(0), len).iter().all(|&n| n < len));
+    assert!(pad.recovered()[len..].iter().all(|&r| !r));
+    assert!(pad.decrypt_partial()[3][len..].iter().all(|b| b.is_none()));
+    assert!(pad.decrypt_partial()[3][..len].iter().all(|b| b.is_some()));
+
+    // known plaintext wins over the heuristics, before and after recovery
+    let wrong = b"Some other text";
+    pad.add_known(Correction { ct: 3, offset: 4, text: wrong.to_vec() }).unwrap();
+    pad.add_known(Correction { ct: 3, offset: len, text: long[3][len..].to_vec() }).unwrap();
+    pad.recover_key(Recovery::Scoring(&English));
+    assert_eq!(&pad.decrypt()[3][4..4 + wrong.len()], wrong);
+    assert_eq!(&pad.key()[len..long[3].len()], &keystream[len..long[3].len()]);
+    assert!(pad.confidence()[4..4 + wrong.len()].iter().all(|&c| c == 1.0));
+    assert!(pad.recovered()[len..long[3].len()].iter().all(|&r| r));
+    assert_eq!(pad.decrypt()[3][len..], long[3][len..]);
+    pad.add_known(Correction { ct: 0, offset: 0, text: b"XYZ".to_vec() }).unwrap();
+    assert_eq!(&pad.decrypt()[0][..3], b"XYZ");
+    assert!(pad.add_known(Correction { ct: 11, offset: 0, text: wrong.to_vec() }).is_err());
+    assert!(pad.add_known(Correction { ct: 0, offset: len - 2, text: wrong.to_vec() }).is_err());
+    assert!(pad.add_known(Correction { ct: 0, offset: usize::MAX, text: wrong.to_vec() }).is_err());
+    assert!(pad.add_known(Correction { ct: 0, offset: usize::MAX - 2, text: wrong.to_vec() }).is_err());
+    assert_eq!(pad.known().len(), 3);
+
+    // cribs past the end of the ciphertext, however far, are refused
+    let mut key = vec![0; len];
+    let ct = &pad.ciphertexts()[0];
+    assert!(crib::commit(&mut key, ct, len - 3, b"abc").is_ok());
+    assert!(crib::commit(&mut key, ct, len - 2, b"abc").is_err());
+    assert!(crib::commit(&mut key, ct, usize::MAX - 1, b"abc").is_err());
+
+    // corrections come back from their file byte for byte
+    let fixes = vec![
+        Correction { ct: 0, offset: 7, text: b"which will ".to_vec() },
+        Correction { ct: 1, offset: 0, text: b"\\x41 \\\\ \\q \r\n\t\x7f\xff\xc3".to_vec() },
+        Correction { ct: 2, offset: 3, text: "caf\u{e9} \u{2014} \u{85}".as_bytes().to_vec() },
+        Correction { ct: 3, offset: 1, text: b"\x89PNG\r\n\x1a\n\x00".to_vec() },
+    ];
+    let path = std::env::temp_dir().join(format!("corrections-{}.txt", std::process::id()));
+    corrections::save(&path, &fixes).unwrap();
+    assert_eq!(corrections::load(&path).unwrap(), fixes);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(corrections::escape(b"a\\b\n\xff"), "a\\\\b\\x0a\\xff");
+    assert_eq!(corrections::unescape("\\x4 \\+f \\x41\\"), b"\\x4 \\+f A\\");
+
+    // pair scores are exact binomial tails
+    let score = |xor: Vec<u8>| detect::pair_score(&xor, &vec![0; xor.len()], 0).1;
+    assert!((score([vec![0x41; 30], vec![0xc1; 10]].concat()) - 2.6533666296883687).abs() < 1e-9);
+    assert!((score([vec![0; 6], vec![0x41; 94], vec![0xc1; 100]].concat()) - 3.513018049543992).abs() < 1e-9);
+    assert!((score(vec![0x41; 16]) - 15.0 * 2.0f64.log10()).abs() < 1e-9);
+    assert!((score(vec![0; 100]) - (100.0 * 256.0f64.log10() - 2.0f64.log10())).abs() < 1e-6);
+
+    // detection: random ciphertexts share nothing with anything
+    let shifts = [0, 5, 17, 2, 30, 11, 0, 23, 8, 1, 14];
+    let random: Vec<Vec<u8>> = (0..100).map(|i| rng.bytes(30 + (i * 37) % 90)).collect();
+    let max_shift = 32;
+    let stats = detect::pair_stats(&random, max_shift, 16);
+    assert!(detect::clusters(random.len(), &stats, detect::threshold(random.len(), max_shift, 3.0)).is_empty());
+
+    // while messages under one keystream, at various offsets in it, are
+    // found among them and nothing else joins them
+    let stream = rng.bytes(200);
+    let mut corpus: Vec<Vec<u8>> = random[..40].to_vec();
+    for (pt, &s) in plaintexts.iter().zip(&shifts) {
+        corpus.push(pt.iter().zip(&stream[s..]).map(|(m, k)| m ^ k).collect());
+    }
+    let stats = detect::pair_stats(&corpus, max_shift, 16);
+    let clusters = detect::clusters(corpus.len(), &stats, detect::threshold(corpus.len(), max_shift, 3.0));
+    assert_eq!(clusters.len(), 1);
+    assert_eq!(clusters[0].members, (40..corpus.len()).collect::<Vec<_>>());
+    assert_eq!(clusters[0].offsets, shifts);
+
+    // repeating-key xor: the key, not a multiple of it
+    let text = MESSAGES.join(" ").into_bytes();
+    for key in [&b"ICEBREAKER"[..], b"Y", b"seven", b"a much longer key here"] {
+        let ct = vigenere::decrypt(key, &text);
+        assert_eq!(vigenere::solve(&ct, 40, &English).unwrap(), key);
+        assert_eq!(vigenere::decrypt(key, &ct), text);
+    }
+
+    println!("self-test passed");
+}
@@ -0,0 +1,62 @@
+use crate::{score_key, PlaintextModel};
+
+// Number of differing bits between two equally long byte strings
+fn hamming(a: &[u8], b: &[u8]) -> u32 {
+    a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum()
+}
+
+// Candidate key lengths from 1 to max_len, most likely first.
+// Two ciphertext blocks one key length apart are encrypted with the same
+// key bytes, so their xor is the xor of two plaintexts and has far fewer
+// set bits than the xor of two random-looking blocks: average the Hamming
+// distance (per bit) over all consecutive block pairs for each length.
+pub fn guess_key_len(ciphertext: &[u8], max_len: usize) -> Vec<(usize, f64)> {
+    let mut candidates: Vec<(usize, f64)> = (1..=max_len.min(ciphertext.len() / 2))
+        .map(|len| {
+            let blocks: Vec<&[u8]> = ciphertext.chunks_exact(len).collect();
+            let pairs = blocks.len() - 1;
+            let dist: u32 = blocks.windows(2).map(|b| hamming(b[0], b[1])).sum();
+            (len, dist as f64 / (pairs * len * 8) as f64)
+        }).collect();
+    candidates.sort_by(|a, b| a.1.total_cmp(&b.1));
+    candidates
+}
+
+// Break a repeating-key xor with known key length: the ciphertext, cut
+// into rows of key_len bytes, is a many-time pad with a key_len long key,
+// so each column is scored exactly as in the many-time pad case
+pub fn break_key(ciphertext: &[u8], key_len: usize, model: &dyn PlaintextModel) -> (Vec<u8>, Vec<f64>) {
+    let rows: Vec<Vec<u8>> = ciphertext.chunks(key_len).map(|r| r.to_vec()).collect();
+    let mut key = vec![0u8; key_len];
+    let mut confidence = vec![0.0; key_len];
+    score_key(&mut key, &mut confidence, &rows, &vec![0; rows.len()], model);
+    (key, confidence)
+}
+
+pub fn decrypt(key: &[u8], ciphertext: &[u8]) -> Vec<u8> {
+    ciphertext.iter().zip(key.iter().cycle()).map(|(c, k)| c ^ k).collect()
+}
+
+// Guess the key length, then try the few best lengths and keep the key
+// whose decryption scores best on average per key byte.
+// Any multiple of the key length breaks just as well (the key repeated),
+// so the winner is cut down to its smallest period.
+pub fn solve(ciphertext: &[u8], max_len: usize, model: &dyn PlaintextModel) -> Option<Vec<u8>> {
+    guess_key_len(ciphertext, max_len).iter()
+        .take(3)
+        .map(|(len, _)| break_key(ciphertext, *len, model))
+        .max_by(|a, b| mean(&a.1).total_cmp(&mean(&b.1)))
+        .map(|(key, _)| key[..period(&key)].to_vec())
+}
+
+// Shortest p such that the key is key[..p] repeated
+fn period(key: &[u8]) -> usize {
+    (1..key.len())
+        .filter(|p| key.len().is_multiple_of(*p))
+        .find(|&p| key.iter().zip(key.iter().cycle().skip(p)).all(|(a, b)| a == b))
+        .unwrap_or(key.len())
+}
+
+fn mean(x: &[f64]) -> f64 {
+    x.iter().sum::<f64>() / x.len() as f64
+}
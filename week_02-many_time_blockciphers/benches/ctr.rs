@@ -0,0 +1,43 @@
+// CTR throughput: the block-by-block encrypt_ctr against the in-place,
+// batched apply_keystream on one thread and on every available core.
+// Run with `cargo bench`.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use week_02_multi_time_blockciphers::{apply_keystream, encrypt_ctr, Aes, CounterLayout};
+
+const DATA_LEN: usize = 16 << 20;
+const MIN_TIME: Duration = Duration::from_secs(2);
+
+// Run f until MIN_TIME has passed, return the throughput in MiB/s
+fn throughput<F: FnMut()>(mut f: F) -> f64 {
+    let start = Instant::now();
+    let mut runs = 0;
+    while start.elapsed() < MIN_TIME {
+        f();
+        runs += 1;
+    }
+    (runs * DATA_LEN) as f64 / (1 << 20) as f64 / start.elapsed().as_secs_f64()
+}
+
+fn main() {
+    let threads = thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let iv = [0x42u8; 16];
+    let mut data = vec![0u8; DATA_LEN];
+
+    println!("{} MiB buffer, {} threads available", DATA_LEN >> 20, threads);
+    for key_len in [16, 32] {
+        let cipher = Aes::new(&vec![7u8; key_len]).unwrap();
+        let name = format!("AES-{}", key_len * 8);
+
+        let speed = throughput(|| { encrypt_ctr(&cipher, &data, Some(&iv)); });
+        println!("{} encrypt_ctr:                 {:8.1} MiB/s", name, speed);
+
+        let speed = throughput(|| apply_keystream(&cipher, CounterLayout::Full128, &iv, &mut data, 1).unwrap());
+        println!("{} apply_keystream, 1 thread:   {:8.1} MiB/s", name, speed);
+
+        let speed = throughput(|| apply_keystream(&cipher, CounterLayout::Full128, &iv, &mut data, threads).unwrap());
+        println!("{} apply_keystream, {:2} threads: {:8.1} MiB/s", name, threads, speed);
+    }
+}
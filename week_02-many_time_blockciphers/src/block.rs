@@ -0,0 +1,89 @@
+use std::fmt;
+
+use aes::{Aes128, Aes192, Aes256, Block};
+use aes::cipher::{BlockDecrypt, BlockEncrypt, BlockSizeUser, KeyInit};
+use aes::cipher::consts::U16;
+
+// Any block cipher with 128-bit blocks, which is all the modes need
+pub trait BlockCipher128 {
+    fn encrypt(&self, block: &mut Block);
+    fn decrypt(&self, block: &mut Block);
+
+    // Several independent blocks at once, which AES-NI and the bitsliced
+    // software AES pipeline much faster than one at a time
+    fn encrypt_many(&self, blocks: &mut [Block]) {
+        blocks.iter_mut().for_each(|b| self.encrypt(b));
+    }
+}
+
+impl<C> BlockCipher128 for C
+    where C: BlockEncrypt + BlockDecrypt + BlockSizeUser<BlockSize = U16> {
+    fn encrypt(&self, block: &mut Block) {
+        self.encrypt_block(block);
+    }
+
+    fn decrypt(&self, block: &mut Block) {
+        self.decrypt_block(block);
+    }
+
+    fn encrypt_many(&self, blocks: &mut [Block]) {
+        self.encrypt_blocks(blocks);
+    }
+}
+
+// Key of a length AES does not have
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidKeyLength(pub usize);
+
+impl fmt::Display for InvalidKeyLength {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid AES key length ({}), expected 16, 24 or 32 bytes", self.0)
+    }
+}
+
+impl std::error::Error for InvalidKeyLength {}
+
+// AES with the key size given by the key
+pub enum Aes {
+    Aes128(Aes128),
+    Aes192(Aes192),
+    Aes256(Aes256),
+}
+
+impl Aes {
+    pub fn new(key: &[u8]) -> Result<Self, InvalidKeyLength> {
+        let invalid = |_| InvalidKeyLength(key.len());
+        match key.len() {
+            16 => { Aes128::new_from_slice(key).map(Aes::Aes128).map_err(invalid) }
+            24 => { Aes192::new_from_slice(key).map(Aes::Aes192).map_err(invalid) }
+            32 => { Aes256::new_from_slice(key).map(Aes::Aes256).map_err(invalid) }
+            len => { Err(InvalidKeyLength(len)) }
+        }
+    }
+}
+
+impl BlockCipher128 for Aes {
+    fn encrypt(&self, block: &mut Block) {
+        match self {
+            Aes::Aes128(c) => { c.encrypt_block(block) }
+            Aes::Aes192(c) => { c.encrypt_block(block) }
+            Aes::Aes256(c) => { c.encrypt_block(block) }
+        }
+    }
+
+    fn decrypt(&self, block: &mut Block) {
+        match self {
+            Aes::Aes128(c) => { c.decrypt_block(block) }
+            Aes::Aes192(c) => { c.decrypt_block(block) }
+            Aes::Aes256(c) => { c.decrypt_block(block) }
+        }
+    }
+
+    fn encrypt_many(&self, blocks: &mut [Block]) {
+        match self {
+            Aes::Aes128(c) => { c.encrypt_blocks(blocks) }
+            Aes::Aes192(c) => { c.encrypt_blocks(blocks) }
+            Aes::Aes256(c) => { c.encrypt_blocks(blocks) }
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use week_02_multi_time_blockciphers::container::{Algorithm, DEFAULT_ITERATIONS, MAX_ITERATIONS};
+
+pub const USAGE: &str = "\
+usage: week_02-multi_time_blockciphers [COMMAND [OPTIONS] FILE...]
+
+Without a command, run the known-answer tests of every mode.
+
+commands:
+  encrypt [OPTIONS] <IN> <OUT>     encrypt IN into a self-describing container
+                                   written to OUT
+  decrypt [OPTIONS] <IN> <OUT>     decrypt the container IN, write the
+                                   plaintext to OUT
+  info <IN>                        print the header of the container IN
+
+options:
+  -k, --key-file <FILE>            hex encoded AES key (two keys for SIV)
+  -p, --passphrase-env <VAR>       derive the key from the passphrase in the
+                                   environment variable VAR (PBKDF2)
+  -a, --algorithm <gcm|siv>        encrypt with AES-GCM or AES-SIV
+                                   (default: gcm)
+  -b, --key-bits <128|192|256>     AES key size with a passphrase, the key
+                                   file decides otherwise (default: 256)
+      --aad <TEXT>                 associated data stored in clear and
+                                   authenticated with the file
+      --iterations <N>             PBKDF2 iterations, at most 6000000
+                                   (default: 600000)
+  -h, --help                       print this help";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Command {
+    Encrypt,
+    Decrypt,
+    Info,
+}
+
+pub struct Options {
+    pub command: Command,
+    pub files: Vec<String>,
+    pub key_file: Option<String>,
+    pub passphrase_env: Option<String>,
+    pub algorithm: Algorithm,
+    pub key_bits: usize,
+    pub aad: Vec<u8>,
+    pub iterations: u32,
+    pub help: bool,
+}
+
+fn value<I: Iterator<Item = String>>(args: &mut I, option: &str) -> Result<String, String> {
+    args.next().ok_or(format!("missing value for {}", option))
+}
+
+pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+    let mut help = false;
+    let command = match args.next().as_deref() {
+        Some("encrypt") => { Command::Encrypt }
+        Some("decrypt") => { Command::Decrypt }
+        Some("info") => { Command::Info }
+        Some("-h") | Some("--help") => { help = true; Command::Info }
+        Some(x) => { return Err(format!("unknown command '{}'", x)); }
+        None => { return Err(String::from("missing command")); }
+    };
+
+    let mut opts = Options {
+        command,
+        files: Vec::new(),
+        key_file: None,
+        passphrase_env: None,
+        algorithm: Algorithm::Gcm,
+        key_bits: 256,
+        aad: Vec::new(),
+        iterations: DEFAULT_ITERATIONS,
+        help,
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-k" | "--key-file" => { opts.key_file = Some(value(&mut args, &arg)?); }
+            "-p" | "--passphrase-env" => { opts.passphrase_env = Some(value(&mut args, &arg)?); }
+            "-a" | "--algorithm" => {
+                opts.algorithm = match value(&mut args, &arg)?.as_str() {
+                    "gcm" => { Algorithm::Gcm }
+                    "siv" => { Algorithm::Siv }
+                    x => { return Err(format!("unknown algorithm '{}'", x)); }
+                };
+            }
+            "-b" | "--key-bits" => {
+                let n = value(&mut args, &arg)?;
+                opts.key_bits = match n.as_str() {
+                    "128" | "192" | "256" => { n.parse().unwrap() }
+                    _ => { return Err(format!("invalid key size '{}'", n)); }
+                };
+            }
+            "--aad" => { opts.aad = value(&mut args, &arg)?.into_bytes(); }
+            "--iterations" => {
+                let n = value(&mut args, &arg)?;
+                opts.iterations = n.parse().ok().filter(|i| (1..=MAX_ITERATIONS).contains(i))
+                                   .ok_or(format!("invalid iterations '{}'", n))?;
+            }
+            "-h" | "--help" => { opts.help = true; }
+            x if x.starts_with('-') => { return Err(format!("unknown option '{}'", x)); }
+            _ => { opts.files.push(arg); }
+        }
+    }
+    if opts.help {
+        return Ok(opts);
+    }
+
+    let expected = if opts.command == Command::Info { 1 } else { 2 };
+    if opts.files.len() != expected {
+        return Err(format!("expected {} file(s), got {}", expected, opts.files.len()));
+    }
+    if opts.command != Command::Info && opts.key_file.is_some() == opts.passphrase_env.is_some() {
+        return Err(String::from("give either --key-file or --passphrase-env"));
+    }
+    Ok(opts)
+}
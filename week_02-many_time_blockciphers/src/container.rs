@@ -0,0 +1,336 @@
+// Self-describing encrypted files. Everything needed to decrypt, except
+// the key or passphrase, is in a header at the start of the file:
+//
+//   magic          4 bytes  "BCEF"
+//   version        1 byte   1
+//   algorithm      1 byte   1 = AES-GCM, 2 = AES-SIV
+//   key length     1 byte   AES key bytes (GCM 16/24/32, SIV 32/48/64)
+//   kdf            1 byte   0 = raw key, 1 = PBKDF2-HMAC-SHA256
+//     iterations   4 bytes  big endian  } PBKDF2 only
+//     salt length  1 byte               }
+//     salt                              }
+//   nonce length   1 byte
+//   nonce
+//   aad length     4 bytes  big endian
+//   aad                     associated data, authenticated, not encrypted
+//
+// followed by the ciphertext, then the 16-byte tag. The whole header is the
+// associated data of the AEAD, so no field of it can be changed unnoticed.
+// Any other version, algorithm or kdf is refused, never guessed.
+
+use std::fmt;
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::siv::Siv;
+use crate::{gcm, random_bytes, Aes, DecryptError, EncryptError, InvalidKeyLength};
+
+pub const MAGIC: &[u8; 4] = b"BCEF";
+pub const VERSION: u8 = 1;
+pub const TAG_LEN: usize = 16;
+// OWASP's recommendation for PBKDF2-HMAC-SHA256
+pub const DEFAULT_ITERATIONS: u32 = 600_000;
+// The key is derived before the tag can be checked, so a forged header
+// could otherwise make opening a file run for hours
+pub const MAX_ITERATIONS: u32 = 10 * DEFAULT_ITERATIONS;
+const SALT_LEN: usize = 16;
+const SIV_NONCE_LEN: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Algorithm {
+    Gcm,
+    Siv,
+}
+
+impl Algorithm {
+    fn id(self) -> u8 {
+        match self {
+            Algorithm::Gcm => { 1 }
+            Algorithm::Siv => { 2 }
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => { Some(Algorithm::Gcm) }
+            2 => { Some(Algorithm::Siv) }
+            _ => { None }
+        }
+    }
+
+    fn valid_key_len(self, len: usize) -> bool {
+        match self {
+            Algorithm::Gcm => { [16, 24, 32].contains(&len) }
+            Algorithm::Siv => { [32, 48, 64].contains(&len) }
+        }
+    }
+
+    fn nonce_len(self) -> usize {
+        match self {
+            Algorithm::Gcm => { gcm::NONCE_LEN }
+            Algorithm::Siv => { SIV_NONCE_LEN }
+        }
+    }
+}
+
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Algorithm::Gcm => { write!(f, "AES-GCM") }
+            Algorithm::Siv => { write!(f, "AES-SIV") }
+        }
+    }
+}
+
+// How the key was obtained
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Kdf {
+    Raw,
+    Pbkdf2Sha256 { iterations: u32, salt: Vec<u8> },
+}
+
+// What the key comes from when sealing or opening
+#[derive(Clone, Copy)]
+pub enum Secret<'a> {
+    Key(&'a [u8]),
+    Passphrase(&'a [u8]),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub version: u8,
+    pub algorithm: Algorithm,
+    pub key_len: usize,
+    pub kdf: Kdf,
+    pub nonce: Vec<u8>,
+    pub aad: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ContainerError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    UnknownAlgorithm(u8),
+    UnknownKdf(u8),
+    // the file ends in the middle of the header or before the tag
+    Truncated,
+    // header field out of range
+    InvalidHeader(&'static str),
+    InvalidKeyLength(usize),
+    // a raw key was given for a passphrase protected file, or the reverse
+    WrongSecret,
+    Encrypt(EncryptError),
+    Decrypt(DecryptError),
+}
+
+impl fmt::Display for ContainerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ContainerError::BadMagic => { write!(f, "Not an encrypted container") }
+            ContainerError::UnsupportedVersion(v) => { write!(f, "Unsupported container version {}", v) }
+            ContainerError::UnknownAlgorithm(a) => { write!(f, "Unknown algorithm {}", a) }
+            ContainerError::UnknownKdf(k) => { write!(f, "Unknown key derivation {}", k) }
+            ContainerError::Truncated => { write!(f, "Truncated container") }
+            ContainerError::InvalidHeader(field) => { write!(f, "Invalid container header: {}", field) }
+            ContainerError::InvalidKeyLength(len) => { write!(f, "{}", InvalidKeyLength(*len)) }
+            ContainerError::WrongSecret => { write!(f, "Key given for a passphrase protected file, or the reverse") }
+            ContainerError::Encrypt(e) => { write!(f, "{}", e) }
+            ContainerError::Decrypt(e) => { write!(f, "{}", e) }
+        }
+    }
+}
+
+impl std::error::Error for ContainerError {}
+
+impl From<EncryptError> for ContainerError {
+    fn from(e: EncryptError) -> Self {
+        ContainerError::Encrypt(e)
+    }
+}
+
+impl From<DecryptError> for ContainerError {
+    fn from(e: DecryptError) -> Self {
+        ContainerError::Decrypt(e)
+    }
+}
+
+impl From<InvalidKeyLength> for ContainerError {
+    fn from(e: InvalidKeyLength) -> Self {
+        ContainerError::InvalidKeyLength(e.0)
+    }
+}
+
+// PBKDF2 (RFC 8018) with HMAC-SHA256 as PRF
+pub fn pbkdf2_sha256(passphrase: &[u8], salt: &[u8], iterations: u32, len: usize) -> Vec<u8>
+{
+    let prf = Hmac::<Sha256>::new_from_slice(passphrase).unwrap();
+    let mut out = Vec::with_capacity(len);
+    for block in 1..=len.div_ceil(32) as u32 {
+        let mut mac = prf.clone();
+        mac.update(salt);
+        mac.update(&block.to_be_bytes());
+        let mut u: [u8; 32] = mac.finalize().into_bytes().into();
+        let mut t = u;
+        for _ in 1..iterations {
+            let mut mac = prf.clone();
+            mac.update(&u);
+            u = mac.finalize().into_bytes().into();
+            t.iter_mut().zip(&u).for_each(|(a, b)| *a ^= b);
+        }
+        out.extend_from_slice(&t);
+    }
+    out.truncate(len);
+    out
+}
+
+impl Header {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&[self.version, self.algorithm.id(), self.key_len as u8]);
+        match &self.kdf {
+            Kdf::Raw => { out.push(0); }
+            Kdf::Pbkdf2Sha256 { iterations, salt } => {
+                out.push(1);
+                out.extend_from_slice(&iterations.to_be_bytes());
+                out.push(salt.len() as u8);
+                out.extend_from_slice(salt);
+            }
+        }
+        out.push(self.nonce.len() as u8);
+        out.extend_from_slice(&self.nonce);
+        out.extend_from_slice(&(self.aad.len() as u32).to_be_bytes());
+        out.extend_from_slice(&self.aad);
+        out
+    }
+
+    // Parse the header at the start of data, returns it with its length
+    pub fn parse(data: &[u8]) -> Result<(Self, usize), ContainerError> {
+        let mut r = Reader { data, pos: 0 };
+        if r.take(MAGIC.len()).map_err(|_| ContainerError::BadMagic)? != MAGIC {
+            return Err(ContainerError::BadMagic);
+        }
+        let version = r.byte()?;
+        if version != VERSION {
+            return Err(ContainerError::UnsupportedVersion(version));
+        }
+        let id = r.byte()?;
+        let algorithm = Algorithm::from_id(id).ok_or(ContainerError::UnknownAlgorithm(id))?;
+        let key_len = r.byte()? as usize;
+        if !algorithm.valid_key_len(key_len) {
+            return Err(ContainerError::InvalidHeader("key length"));
+        }
+        let kdf = match r.byte()? {
+            0 => { Kdf::Raw }
+            1 => {
+                let iterations = u32::from_be_bytes(r.take(4)?.try_into().unwrap());
+                if !(1..=MAX_ITERATIONS).contains(&iterations) {
+                    return Err(ContainerError::InvalidHeader("PBKDF2 iterations"));
+                }
+                let salt_len = r.byte()? as usize;
+                Kdf::Pbkdf2Sha256 { iterations, salt: r.take(salt_len)?.to_vec() }
+            }
+            k => { return Err(ContainerError::UnknownKdf(k)); }
+        };
+        let nonce_len = r.byte()? as usize;
+        if nonce_len != algorithm.nonce_len() {
+            return Err(ContainerError::InvalidHeader("nonce length"));
+        }
+        let nonce = r.take(nonce_len)?.to_vec();
+        let aad_len = u32::from_be_bytes(r.take(4)?.try_into().unwrap()) as usize;
+        let aad = r.take(aad_len)?.to_vec();
+
+        Ok((Header { version, algorithm, key_len, kdf, nonce, aad }, r.pos))
+    }
+
+    fn key(&self, secret: Secret) -> Result<Vec<u8>, ContainerError> {
+        match (&self.kdf, secret) {
+            (Kdf::Raw, Secret::Key(key)) if key.len() == self.key_len => { Ok(key.to_vec()) }
+            (Kdf::Raw, Secret::Key(key)) => { Err(ContainerError::InvalidKeyLength(key.len())) }
+            (Kdf::Pbkdf2Sha256 { iterations, salt }, Secret::Passphrase(p)) => {
+                Ok(pbkdf2_sha256(p, salt, *iterations, self.key_len))
+            }
+            _ => { Err(ContainerError::WrongSecret) }
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ContainerError> {
+        let bytes = self.data.get(self.pos..self.pos + n).ok_or(ContainerError::Truncated)?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, ContainerError> {
+        Ok(self.take(1)?[0])
+    }
+}
+
+// Encrypt into a new container. A passphrase goes through PBKDF2 with a
+// new random salt and the given iterations (1 to MAX_ITERATIONS, ignored
+// for a raw key), a raw key must be key_len long.
+pub fn seal(secret: Secret, algorithm: Algorithm, key_len: usize, iterations: u32, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, ContainerError>
+{
+    if !algorithm.valid_key_len(key_len) {
+        return Err(ContainerError::InvalidKeyLength(key_len));
+    }
+    if !(1..=MAX_ITERATIONS).contains(&iterations) && matches!(secret, Secret::Passphrase(_)) {
+        return Err(ContainerError::InvalidHeader("PBKDF2 iterations"));
+    }
+    if aad.len() > u32::MAX as usize {
+        return Err(ContainerError::InvalidHeader("associated data too long"));
+    }
+    let kdf = match secret {
+        Secret::Key(_) => { Kdf::Raw }
+        Secret::Passphrase(_) => { Kdf::Pbkdf2Sha256 { iterations, salt: random_bytes(SALT_LEN) } }
+    };
+    let header = Header { version: VERSION, algorithm, key_len, kdf, nonce: random_bytes(algorithm.nonce_len()), aad: aad.to_vec() };
+    let key = header.key(secret)?;
+    let header_bytes = header.to_bytes();
+
+    let mut out = header_bytes.clone();
+    match algorithm {
+        Algorithm::Gcm => {
+            // files past gcm::MAX_LEN are refused
+            let (ciphertext, tag) = gcm::seal(&Aes::new(&key)?, &header.nonce, &header_bytes, plaintext)?;
+            out.extend_from_slice(&ciphertext);
+            out.extend_from_slice(&tag);
+        }
+        Algorithm::Siv => {
+            // V || C from SIV, stored as C || V like the GCM tag
+            let sealed = Siv::new(&key)?.encrypt(&[&header_bytes], plaintext);
+            out.extend_from_slice(&sealed[TAG_LEN..]);
+            out.extend_from_slice(&sealed[..TAG_LEN]);
+        }
+    }
+    Ok(out)
+}
+
+// Decrypt a container, returning its header (for the associated data)
+// and the plaintext
+pub fn open(secret: Secret, container: &[u8]) -> Result<(Header, Vec<u8>), ContainerError>
+{
+    let (header, header_len) = Header::parse(container)?;
+    let (header_bytes, body) = container.split_at(header_len);
+    if body.len() < TAG_LEN {
+        return Err(ContainerError::Truncated);
+    }
+    let (ciphertext, tag) = body.split_at(body.len() - TAG_LEN);
+    let key = header.key(secret)?;
+
+    let plaintext = match header.algorithm {
+        Algorithm::Gcm => { gcm::open(&Aes::new(&key)?, &header.nonce, header_bytes, ciphertext, tag)? }
+        Algorithm::Siv => {
+            let mut sealed = tag.to_vec();
+            sealed.extend_from_slice(ciphertext);
+            Siv::new(&key)?.decrypt(&[header_bytes], &sealed)?
+        }
+    };
+    Ok((header, plaintext))
+}
@@ -0,0 +1,70 @@
+// How the IV of a CTR message is split between a fixed nonce and a
+// counter incremented once per block.
+// Letting a split counter wrap would either carry into the nonce (so that
+// messages with consecutive nonces share keystream) or come back to the
+// first counter value of the message: both reuse keystream blocks, which is
+// the two time pad all over again. Such messages are refused instead.
+
+use std::fmt;
+
+use crate::{DecryptError, EncryptError};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CounterLayout {
+    // the whole block is a big endian counter, incremented modulo 2^128
+    // (no message is ever long enough to come back to its start)
+    Full128,
+    // 96-bit nonce then 32-bit counter, as in GCM
+    Nonce96Counter32,
+    // 64-bit nonce then 64-bit counter
+    Nonce64Counter64,
+}
+
+// The message needs more blocks than its counter can count from the IV
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CounterOverflow;
+
+impl fmt::Display for CounterOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CTR counter would wrap within the message")
+    }
+}
+
+impl std::error::Error for CounterOverflow {}
+
+impl From<CounterOverflow> for DecryptError {
+    fn from(_: CounterOverflow) -> Self {
+        DecryptError::CounterOverflow
+    }
+}
+
+impl From<CounterOverflow> for EncryptError {
+    fn from(_: CounterOverflow) -> Self {
+        EncryptError::CounterOverflow
+    }
+}
+
+impl CounterLayout {
+    // Counter block of block i of a message starting at iv
+    pub fn nth(self, iv: u128, i: u128) -> Result<u128, CounterOverflow> {
+        let bits = match self {
+            CounterLayout::Full128 => { return Ok(iv.wrapping_add(i)); }
+            CounterLayout::Nonce96Counter32 => { 32 }
+            CounterLayout::Nonce64Counter64 => { 64 }
+        };
+        let mask = (1u128 << bits) - 1;
+        let counter = iv & mask;
+        if i > mask - counter {
+            return Err(CounterOverflow);
+        }
+        Ok((iv & !mask) | (counter + i))
+    }
+
+    // Check that a message of len bytes fits before the counter wraps
+    pub fn check(self, iv: u128, len: usize) -> Result<(), CounterOverflow> {
+        match len.div_ceil(crate::BLOCK_LEN) {
+            0 => { Ok(()) }
+            blocks => { self.nth(iv, blocks as u128 - 1).map(|_| ()) }
+        }
+    }
+}
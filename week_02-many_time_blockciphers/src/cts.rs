@@ -0,0 +1,88 @@
+// CBC with ciphertext stealing, CS3 variant (NIST SP 800-38A addendum, as
+// used by Kerberos in RFC 3962): no padding, the ciphertext is exactly as
+// long as the plaintext, which has to be at least one block long.
+// The last plaintext block is zero padded and everything is CBC encrypted,
+// then the last two ciphertext blocks are swapped and the (now last)
+// second to last one is cut to the length of the last plaintext block:
+// the bytes cut are not lost, decrypting C_n gives them back.
+
+use std::fmt;
+
+use crate::{cbc_step_dec, cbc_step_enc, iv_or_random, split_iv, BlockCipher128, DecryptError, EncryptError, BLOCK_LEN};
+
+// Ciphertext stealing needs at least one full block to steal from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlaintextTooShort(pub usize);
+
+impl fmt::Display for PlaintextTooShort {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Plaintext too short for ciphertext stealing ({} bytes, at least {} needed)", self.0, BLOCK_LEN)
+    }
+}
+
+impl std::error::Error for PlaintextTooShort {}
+
+// nonce is the first block of ciphertext
+// if the provided one is None, a new one is created
+pub fn encrypt_cbc_cs3<C: BlockCipher128 + ?Sized>(cipher: &C, plaintext: &[u8], nonce: Option<&[u8]>) -> Result<Vec<u8>, EncryptError>
+{
+    if plaintext.len() < BLOCK_LEN {
+        return Err(EncryptError::PlaintextTooShort(plaintext.len()));
+    }
+    let mut ciphertext = iv_or_random(nonce)?;
+
+    let mut blocks: Vec<Vec<u8>> = Vec::new();
+    let mut prev = ciphertext.clone();
+    for pt_block in plaintext.chunks(BLOCK_LEN) {
+        let mut block = pt_block.to_vec();
+        block.resize(BLOCK_LEN, 0);
+        prev = cbc_step_enc(cipher, &prev, &block);
+        blocks.push(prev.clone());
+    }
+
+    let n = blocks.len();
+    if n > 1 {
+        // C_1 ... C_(n-2) || C_n || C_(n-1) cut to the last plaintext block length
+        let last_len = plaintext.len() - (n - 1) * BLOCK_LEN;
+        blocks.swap(n - 2, n - 1);
+        blocks[n - 1].truncate(last_len);
+    }
+    blocks.iter().for_each(|b| ciphertext.extend_from_slice(b));
+    Ok(ciphertext)
+}
+
+// nonce is the first block of ciphertext
+pub fn decrypt_cbc_cs3<C: BlockCipher128 + ?Sized>(cipher: &C, ciphertext: &[u8]) -> Result<Vec<u8>, DecryptError>
+{
+    let (iv, body) = split_iv(ciphertext)?;
+    if body.len() < BLOCK_LEN {
+        return Err(DecryptError::BadLength(ciphertext.len()));
+    }
+    if body.len() == BLOCK_LEN {
+        return Ok(cbc_step_dec(cipher, iv, body));
+    }
+
+    let n = body.len().div_ceil(BLOCK_LEN);
+    let last_len = body.len() - (n - 1) * BLOCK_LEN;
+    let (head, tail) = body.split_at((n - 2) * BLOCK_LEN);
+    let (c_n, c_stolen) = tail.split_at(BLOCK_LEN);
+
+    // plain CBC up to M_(n-2)
+    let mut plaintext: Vec<u8> = Vec::with_capacity(body.len());
+    let mut prev = iv;
+    for block in head.chunks(BLOCK_LEN) {
+        plaintext.extend_from_slice(&cbc_step_dec(cipher, prev, block));
+        prev = block;
+    }
+
+    // Dec[k, C_n] = C_(n-1) ^ (M_n || 0...0): its tail is the tail of
+    // C_(n-1) which was cut off, its head xored with what was kept is M_n
+    let d = cbc_step_dec(cipher, &[0u8; BLOCK_LEN], c_n);
+    let mut c_n1 = c_stolen.to_vec();
+    c_n1.extend_from_slice(&d[last_len..]);
+    let m_n: Vec<u8> = d[..last_len].iter().zip(c_stolen).map(|(a, b)| a ^ b).collect();
+
+    plaintext.extend_from_slice(&cbc_step_dec(cipher, prev, &c_n1));
+    plaintext.extend_from_slice(&m_n);
+    Ok(plaintext)
+}
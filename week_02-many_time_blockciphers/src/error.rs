@@ -0,0 +1,57 @@
+use std::fmt;
+
+use crate::cts::PlaintextTooShort;
+
+// Why a ciphertext could not be decrypted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecryptError {
+    // ciphertext shorter than the IV
+    MissingIv,
+    // ciphertext (IV included) is not a whole number of blocks,
+    // or has no block after the IV
+    BadLength(usize),
+    // the last plaintext block does not end with valid PKCS#7 padding
+    InvalidPadding,
+    // the authentication tag does not match: the ciphertext, the associated
+    // data or the nonce were tampered with (or the key is wrong)
+    InvalidTag,
+    // the CTR counter would wrap before the end of the ciphertext
+    CounterOverflow,
+}
+
+impl fmt::Display for DecryptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecryptError::MissingIv => { write!(f, "Missing IV") }
+            DecryptError::BadLength(len) => { write!(f, "Invalid ciphertext length ({})", len) }
+            DecryptError::InvalidPadding => { write!(f, "Invalid padding") }
+            DecryptError::InvalidTag => { write!(f, "Authentication failed") }
+            DecryptError::CounterOverflow => { write!(f, "CTR counter would wrap within the message") }
+        }
+    }
+}
+
+impl std::error::Error for DecryptError {}
+
+// Why a plaintext could not be encrypted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncryptError {
+    // the given nonce has a length the mode does not take
+    InvalidNonceLength(usize),
+    // the CTR counter would wrap before the end of the plaintext
+    CounterOverflow,
+    // ciphertext stealing needs at least one block of plaintext
+    PlaintextTooShort(usize),
+}
+
+impl fmt::Display for EncryptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EncryptError::InvalidNonceLength(len) => { write!(f, "Invalid nonce length ({})", len) }
+            EncryptError::CounterOverflow => { write!(f, "CTR counter would wrap within the message") }
+            EncryptError::PlaintextTooShort(len) => { write!(f, "{}", PlaintextTooShort(*len)) }
+        }
+    }
+}
+
+impl std::error::Error for EncryptError {}
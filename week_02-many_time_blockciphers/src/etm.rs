@@ -0,0 +1,81 @@
+// Encrypt-then-MAC: CBC or CTR encryption, then HMAC-SHA256 over
+// IV || ciphertext. The MAC is checked before anything is decrypted, so a
+// tampered ciphertext never reaches the CBC padding check (no padding
+// oracle) and never gets decrypted at all.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::{ct_eq, decrypt_cbc, decrypt_ctr, encrypt_cbc, encrypt_ctr_with, iv_or_random};
+use crate::{Aes, CounterLayout, DecryptError, EncryptError, InvalidKeyLength, BLOCK_LEN};
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const TAG_LEN: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Cbc,
+    Ctr,
+}
+
+// Independent encryption and MAC keys, both derived from one master key
+// as HMAC-SHA256(master, label): the master key is never used directly,
+// and knowing one derived key tells nothing about the other.
+pub struct EtmKeys {
+    cipher: Aes,
+    mac_key: [u8; TAG_LEN],
+}
+
+fn derive(master: &[u8], label: &[u8]) -> [u8; TAG_LEN]
+{
+    let mut mac = HmacSha256::new_from_slice(master).unwrap();
+    mac.update(label);
+    mac.finalize().into_bytes().into()
+}
+
+impl EtmKeys {
+    // the encryption key is as long as the master key, 16, 24 or 32 bytes
+    pub fn derive(master: &[u8]) -> Result<Self, InvalidKeyLength> {
+        let enc_key = derive(master, b"encryption key");
+        let cipher = Aes::new(enc_key.get(..master.len()).ok_or(InvalidKeyLength(master.len()))?)?;
+        Ok(Self { cipher, mac_key: derive(master, b"mac key") })
+    }
+
+    fn tag(&self, data: &[u8]) -> [u8; TAG_LEN] {
+        let mut mac = HmacSha256::new_from_slice(&self.mac_key).unwrap();
+        mac.update(data);
+        mac.finalize().into_bytes().into()
+    }
+}
+
+// IV || ciphertext || tag
+// if the provided nonce is None, a new one is created
+pub fn encrypt_etm(keys: &EtmKeys, mode: Mode, plaintext: &[u8], nonce: Option<&[u8]>) -> Result<Vec<u8>, EncryptError>
+{
+    let mut out = match mode {
+        Mode::Cbc => { encrypt_cbc(&keys.cipher, plaintext, Some(&iv_or_random(nonce)?)) }
+        Mode::Ctr => { encrypt_ctr_with(&keys.cipher, CounterLayout::Full128, plaintext, nonce)? }
+    };
+    let tag = keys.tag(&out);
+    out.extend_from_slice(&tag);
+    Ok(out)
+}
+
+pub fn decrypt_etm(keys: &EtmKeys, mode: Mode, ciphertext: &[u8]) -> Result<Vec<u8>, DecryptError>
+{
+    if ciphertext.len() < BLOCK_LEN {
+        return Err(DecryptError::MissingIv);
+    }
+    if ciphertext.len() < BLOCK_LEN + TAG_LEN {
+        return Err(DecryptError::BadLength(ciphertext.len()));
+    }
+    let (body, tag) = ciphertext.split_at(ciphertext.len() - TAG_LEN);
+    if !ct_eq(&keys.tag(body), tag) {
+        return Err(DecryptError::InvalidTag);
+    }
+    match mode {
+        Mode::Cbc => { decrypt_cbc(&keys.cipher, body) }
+        Mode::Ctr => { decrypt_ctr(&keys.cipher, body) }
+    }
+}
@@ -0,0 +1,175 @@
+// Galois/Counter Mode (NIST SP 800-38D): CTR encryption with the counter
+// starting right after the nonce block J0, plus a GHASH (polynomial
+// evaluation in GF(2^128) at the secret point H = Enc[k, 0]) over the
+// associated data and the ciphertext, masked by Enc[k, J0] to make the tag.
+
+use crate::{ct_eq, ctr_step, random_bytes, BlockCipher128, CounterLayout, CounterOverflow, DecryptError, EncryptError, BLOCK_LEN};
+
+// the nonce size GCM is designed for, other sizes go through GHASH
+pub const NONCE_LEN: usize = 12;
+pub const TAG_LEN: usize = 16;
+// longest plaintext under one nonce, 2^39 - 256 bits
+pub const MAX_LEN: u64 = (1 << 36) - 32;
+
+// x^128 = x^7 + x^2 + x + 1, in GCM's reflected bit order
+const R: u128 = 0xe1 << 120;
+
+// Product in GF(2^128). GCM numbers the bits of a block from the most
+// significant bit of its first byte, which is bit 127 of the big endian
+// u128, and that bit is the coefficient of x^0.
+// No branch nor lookup depends on the operands, as H is secret.
+fn gf_mul(x: u128, y: u128) -> u128
+{
+    let mut z = 0u128;
+    let mut v = y;
+    for i in (0..128).rev() {
+        z ^= v & ((x >> i) & 1).wrapping_neg();
+        v = (v >> 1) ^ (R & (v & 1).wrapping_neg());
+    }
+    z
+}
+
+// Up to 16 bytes as a block, zero padded on the right
+fn to_block(bytes: &[u8]) -> u128
+{
+    let mut block = [0u8; BLOCK_LEN];
+    block[..bytes.len()].copy_from_slice(bytes);
+    u128::from_be_bytes(block)
+}
+
+struct GHash {
+    h: u128,
+    y: u128,
+}
+
+impl GHash {
+    fn new(h: u128) -> Self {
+        Self { h, y: 0 }
+    }
+
+    // absorb data, zero padded to whole blocks
+    fn update(&mut self, data: &[u8]) {
+        for chunk in data.chunks(BLOCK_LEN) {
+            self.y = gf_mul(self.y ^ to_block(chunk), self.h);
+        }
+    }
+
+    // absorb the bit lengths of the two inputs, then output
+    fn finish(mut self, len_a: usize, len_c: usize) -> u128 {
+        let lengths = ((len_a as u128 * 8) << 64) | (len_c as u128 * 8);
+        self.y = gf_mul(self.y ^ lengths, self.h);
+        self.y
+    }
+}
+
+fn hash_key<C: BlockCipher128 + ?Sized>(cipher: &C) -> u128
+{
+    to_block(&ctr_step(cipher, 0, &[0u8; BLOCK_LEN]))
+}
+
+// Pre-counter block: the nonce followed by a 32-bit counter set to 1 for
+// 96-bit nonces, the GHASH of the nonce for any other length
+fn pre_counter(h: u128, nonce: &[u8]) -> u128
+{
+    if nonce.len() == NONCE_LEN {
+        return to_block(nonce) | 1;
+    }
+    let mut ghash = GHash::new(h);
+    ghash.update(nonce);
+    ghash.finish(0, nonce.len())
+}
+
+// Start of the keystream, inc32(J0), for a text of len bytes. The spec
+// caps texts at 2^32 - 2 blocks, and lets the 32-bit counter wrap after
+// J0 from a longer nonce: both are refused here.
+fn first_counter(j0: u128, len: usize) -> Result<u128, CounterOverflow>
+{
+    if len as u64 > MAX_LEN {
+        return Err(CounterOverflow);
+    }
+    // J0 masks the tag, the text takes the blocks after it
+    CounterLayout::Nonce96Counter32.check(j0, len + BLOCK_LEN)?;
+    // carries into the nonce only for an empty text, which uses no block
+    Ok(j0.wrapping_add(1))
+}
+
+// CTR encryption/decryption from the first counter block, which
+// first_counter has checked never carries out of the low 32 bits
+fn gctr<C: BlockCipher128 + ?Sized>(cipher: &C, first: u128, data: &[u8]) -> Vec<u8>
+{
+    data.chunks(BLOCK_LEN)
+        .enumerate()
+        .flat_map(|(i, block)| ctr_step(cipher, first + i as u128, block))
+        .collect()
+}
+
+fn tag<C: BlockCipher128 + ?Sized>(cipher: &C, h: u128, j0: u128, aad: &[u8], ciphertext: &[u8]) -> [u8; TAG_LEN]
+{
+    let mut ghash = GHash::new(h);
+    ghash.update(aad);
+    ghash.update(ciphertext);
+    let s = ghash.finish(aad.len(), ciphertext.len());
+    ctr_step(cipher, j0, &s.to_be_bytes()).try_into().unwrap()
+}
+
+// Encrypt the plaintext and authenticate it together with the associated
+// data, returning ciphertext and tag. A nonce must never be used twice
+// with the same key: that gives away the xor of the plaintexts, and H.
+// The nonce may have any length but 0, the plaintext up to MAX_LEN bytes.
+pub fn seal<C: BlockCipher128 + ?Sized>(cipher: &C, nonce: &[u8], aad: &[u8], plaintext: &[u8]) -> Result<(Vec<u8>, [u8; TAG_LEN]), EncryptError>
+{
+    if nonce.is_empty() {
+        return Err(EncryptError::InvalidNonceLength(0));
+    }
+    let h = hash_key(cipher);
+    let j0 = pre_counter(h, nonce);
+    let first = first_counter(j0, plaintext.len())?;
+    let ciphertext = gctr(cipher, first, plaintext);
+    let tag = tag(cipher, h, j0, aad, &ciphertext);
+    Ok((ciphertext, tag))
+}
+
+// Check the tag, then decrypt: nothing is decrypted from a forged ciphertext
+pub fn open<C: BlockCipher128 + ?Sized>(cipher: &C, nonce: &[u8], aad: &[u8], ciphertext: &[u8], tag: &[u8]) -> Result<Vec<u8>, DecryptError>
+{
+    if nonce.is_empty() {
+        return Err(DecryptError::MissingIv);
+    }
+    let h = hash_key(cipher);
+    let j0 = pre_counter(h, nonce);
+    let first = first_counter(j0, ciphertext.len())?;
+    if !ct_eq(&self::tag(cipher, h, j0, aad, ciphertext), tag) {
+        return Err(DecryptError::InvalidTag);
+    }
+    Ok(gctr(cipher, first, ciphertext))
+}
+
+// nonce || ciphertext || tag, with a 96-bit nonce
+// if the provided one is None, a new one is created
+pub fn encrypt_gcm<C: BlockCipher128 + ?Sized>(cipher: &C, plaintext: &[u8], aad: &[u8], nonce: Option<&[u8]>) -> Result<Vec<u8>, EncryptError>
+{
+    let mut out = match nonce {
+        None => { random_bytes(NONCE_LEN) }
+        Some(x) if x.len() == NONCE_LEN => { x.to_vec() }
+        Some(x) => { return Err(EncryptError::InvalidNonceLength(x.len())); }
+    };
+
+    let (ciphertext, tag) = seal(cipher, &out, aad, plaintext)?;
+    out.extend_from_slice(&ciphertext);
+    out.extend_from_slice(&tag);
+    Ok(out)
+}
+
+// nonce is the first NONCE_LEN bytes, tag the last TAG_LEN
+pub fn decrypt_gcm<C: BlockCipher128 + ?Sized>(cipher: &C, ciphertext: &[u8], aad: &[u8]) -> Result<Vec<u8>, DecryptError>
+{
+    if ciphertext.len() < NONCE_LEN {
+        return Err(DecryptError::MissingIv);
+    }
+    if ciphertext.len() < NONCE_LEN + TAG_LEN {
+        return Err(DecryptError::BadLength(ciphertext.len()));
+    }
+    let (nonce, rest) = ciphertext.split_at(NONCE_LEN);
+    let (body, tag) = rest.split_at(rest.len() - TAG_LEN);
+    open(cipher, nonce, aad, body, tag)
+}
@@ -0,0 +1,379 @@
+use rand::RngCore;
+use rand::rngs::OsRng;
+
+pub mod block;
+pub mod container;
+pub mod counter;
+pub mod cts;
+pub mod error;
+pub mod etm;
+pub mod gcm;
+pub mod mac;
+pub mod padding;
+pub mod siv;
+pub mod stream;
+pub mod xts;
+
+pub use block::{Aes, BlockCipher128, InvalidKeyLength};
+pub use counter::{CounterLayout, CounterOverflow};
+pub use error::{DecryptError, EncryptError};
+pub use cts::{decrypt_cbc_cs3, encrypt_cbc_cs3};
+pub use gcm::{decrypt_gcm, encrypt_gcm};
+
+pub const BLOCK_LEN: usize = 16;
+type AesBlock = aes::Block;
+
+// Create random initial value (nonce)
+fn get_rand_iv() -> Vec<u8>
+{
+    random_bytes(BLOCK_LEN)
+}
+
+// The caller's nonce if it is one block, else a random one
+fn iv_or_random(nonce: Option<&[u8]>) -> Result<Vec<u8>, EncryptError>
+{
+    match nonce {
+        None => { Ok(get_rand_iv()) }
+        Some(x) if x.len() == BLOCK_LEN => { Ok(x.to_vec()) }
+        Some(x) => { Err(EncryptError::InvalidNonceLength(x.len())) }
+    }
+}
+
+fn random_bytes(len: usize) -> Vec<u8>
+{
+    let mut csprng = OsRng;
+    let mut bytes = vec![0u8; len];
+    csprng.fill_bytes(&mut bytes);
+    bytes
+}
+
+// Compare two byte strings in time depending only on their lengths,
+// so that a forger cannot learn how many leading bytes of a tag are right
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool
+{
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y));
+    std::hint::black_box(diff) == 0
+}
+
+// Given an AES block x_a and a slice x_b, return a Vec with the xor of the two
+fn xor(x_a: &AesBlock, x_b: &[u8]) -> Vec<u8>
+{
+    x_a.iter().zip(x_b).map(|(a, b)| a^b).collect()
+}
+
+// All the modes below take the block cipher already keyed: an Aes
+// (key size picked from the key length) or any 128-bit block cipher such as
+// aes::Aes256, so that the key schedule is computed once per key.
+
+// Split IV || ciphertext, the IV being a whole block
+fn split_iv(ciphertext: &[u8]) -> Result<(&[u8], &[u8]), DecryptError>
+{
+    if ciphertext.len() < BLOCK_LEN {
+        return Err(DecryptError::MissingIv);
+    }
+    Ok(ciphertext.split_at(BLOCK_LEN))
+}
+
+// Decryption step (i) for CBC:
+// given two consecutive blocks of ciphertext, C_(i-1) and C_i,
+// return M_i = C_(i-1) ^ Dec[k, C_i]
+fn cbc_step_dec<C: BlockCipher128 + ?Sized>(cipher: &C, prev_block: &[u8], curr_block: &[u8]) -> Vec<u8>
+{
+    let mut d = AesBlock::clone_from_slice(curr_block);
+    cipher.decrypt(&mut d);
+    xor(&d, prev_block)
+}
+
+// nonce is the first block of ciphertext
+// the PKCS#7 padding is checked and removed
+pub fn decrypt_cbc<C: BlockCipher128 + ?Sized>(cipher: &C, ciphertext: &[u8]) -> Result<Vec<u8>, DecryptError>
+{
+    let (_, body) = split_iv(ciphertext)?;
+    if body.is_empty() || !body.len().is_multiple_of(BLOCK_LEN) {
+        return Err(DecryptError::BadLength(ciphertext.len()));
+    }
+
+    let plaintext: Vec<u8> = ciphertext.chunks(BLOCK_LEN)
+                                       .collect::<Vec<_>>()
+                                       .windows(2)
+                                       .flat_map(|b| { cbc_step_dec(cipher, b[0], b[1]) })
+                                       .collect();
+
+    padding::unpad(plaintext)
+}
+
+// Encryption step (i) for CBC:
+// given the block of ciphertext, C_(i-1) and of plaintext M_i,
+// return C_i = Enc[k, C_(i-1) ^ M_i]
+fn cbc_step_enc<C: BlockCipher128 + ?Sized>(cipher: &C, prev_block: &[u8], curr_block: &[u8]) -> Vec<u8>
+{
+    let x = xor(AesBlock::from_slice(prev_block), curr_block);
+    let mut d = AesBlock::clone_from_slice(x.as_slice());
+    cipher.encrypt(&mut d);
+    d.to_vec()
+}
+
+// nonce is the first block of ciphertext
+// if the provided one is None, a new one is created
+pub fn encrypt_cbc<C: BlockCipher128 + ?Sized>(cipher: &C, plaintext: &[u8], nonce: Option<&[u8]>) -> Vec<u8>
+{
+    let mut ciphertext= match nonce {
+        None => { get_rand_iv() }
+        Some(x) => { x.to_vec() }
+    };
+
+    padding::pad(plaintext)
+                    .chunks(BLOCK_LEN)
+                    .scan(ciphertext.clone(), |ct, pt_block| {
+                        let next_ct = cbc_step_enc(cipher, ct, pt_block);
+                        *ct = next_ct.to_vec();
+                        Some(next_ct)
+                    }).for_each(|b| ciphertext.extend_from_slice(&b));
+
+    ciphertext
+}
+
+// Encryption/Decryption step (i) for CTR:
+// given nonce iv and ciphertext block C_i
+// return M_i = Enc[k, iv] ^ C_i
+fn ctr_step<C: BlockCipher128 + ?Sized>(cipher: &C, iv: u128, ct_block: &[u8]) -> Vec<u8>
+{
+    let mut pad = AesBlock::from(iv.to_be_bytes());
+    cipher.encrypt(&mut pad);
+    xor(&pad, ct_block)
+}
+
+// CTR over data with the given counter layout, checking first that the
+// counter does not wrap before the end.
+// The callers made sure iv is one block.
+fn ctr<C: BlockCipher128 + ?Sized>(cipher: &C, layout: CounterLayout, iv: &[u8], data: &[u8]) -> Result<Vec<u8>, CounterOverflow>
+{
+    let iv = u128::from_be_bytes(iv.try_into().unwrap());
+    layout.check(iv, data.len())?;
+
+    Ok(data.chunks(BLOCK_LEN)
+        .enumerate()
+        .flat_map(|(i, block)| {
+            ctr_step(cipher, layout.nth(iv, i as u128).unwrap(), block)
+        }).collect())
+}
+
+// nonce is the first block of ciphertext
+pub fn decrypt_ctr_with<C: BlockCipher128 + ?Sized>(cipher: &C, layout: CounterLayout, ciphertext: &[u8]) -> Result<Vec<u8>, DecryptError>
+{
+    let (iv, body) = split_iv(ciphertext)?;
+    Ok(ctr(cipher, layout, iv, body)?)
+}
+
+// nonce is the first block of ciphertext
+// if the provided one is None, a new one is created
+pub fn encrypt_ctr_with<C: BlockCipher128 + ?Sized>(cipher: &C, layout: CounterLayout, plaintext: &[u8], nonce: Option<&[u8]>) -> Result<Vec<u8>, EncryptError>
+{
+    let mut ciphertext = iv_or_random(nonce)?;
+
+    let body = ctr(cipher, layout, &ciphertext, plaintext)?;
+    ciphertext.extend_from_slice(&body);
+    Ok(ciphertext)
+}
+
+// whole block counter, see CounterLayout::Full128
+pub fn decrypt_ctr<C: BlockCipher128 + ?Sized>(cipher: &C, ciphertext: &[u8]) -> Result<Vec<u8>, DecryptError>
+{
+    decrypt_ctr_with(cipher, CounterLayout::Full128, ciphertext)
+}
+
+// whole block counter, see CounterLayout::Full128
+pub fn encrypt_ctr<C: BlockCipher128 + ?Sized>(cipher: &C, plaintext: &[u8], nonce: Option<&[u8]>) -> Vec<u8>
+{
+    // a full 128-bit counter never wraps within a message, and as with
+    // encrypt_cbc a nonce must be a whole block
+    encrypt_ctr_with(cipher, CounterLayout::Full128, plaintext, nonce).expect("CTR nonce must be one block")
+}
+
+// counter blocks encrypted together by apply_keystream
+const BATCH_BLOCKS: usize = 64;
+// below this many bytes per thread, spawning costs more than it saves
+const MIN_THREAD_LEN: usize = 64 * 1024;
+
+// Xor data with the keystream starting at block first of the message
+fn xor_keystream<C: BlockCipher128 + ?Sized>(cipher: &C, layout: CounterLayout, iv: u128, first: usize, data: &mut [u8])
+{
+    let mut blocks = [AesBlock::default(); BATCH_BLOCKS];
+    for (n, chunk) in data.chunks_mut(BATCH_BLOCKS * BLOCK_LEN).enumerate() {
+        let count = chunk.len().div_ceil(BLOCK_LEN);
+        for (j, block) in blocks[..count].iter_mut().enumerate() {
+            let i = first + n * BATCH_BLOCKS + j;
+            // the caller checked the whole message fits
+            *block = AesBlock::from(layout.nth(iv, i as u128).unwrap().to_be_bytes());
+        }
+        cipher.encrypt_many(&mut blocks[..count]);
+        chunk.iter_mut().zip(blocks.iter().flatten()).for_each(|(d, k)| *d ^= k);
+    }
+}
+
+// CTR in place: encrypts or decrypts data (without IV) with the keystream
+// starting at iv. Counter blocks are encrypted BATCH_BLOCKS at a time, and
+// with threads > 1 large buffers are split across that many threads, each
+// one starting at its own block offset in the keystream.
+// iv must be one block, data is left untouched on any error.
+pub fn apply_keystream<C>(cipher: &C, layout: CounterLayout, iv: &[u8], data: &mut [u8], threads: usize) -> Result<(), EncryptError>
+    where C: BlockCipher128 + Sync + ?Sized
+{
+    let iv = u128::from_be_bytes(iv.try_into().map_err(|_| EncryptError::InvalidNonceLength(iv.len()))?);
+    layout.check(iv, data.len())?;
+
+    let threads = threads.min(data.len() / MIN_THREAD_LEN).max(1);
+    if threads == 1 {
+        xor_keystream(cipher, layout, iv, 0, data);
+        return Ok(());
+    }
+
+    // whole blocks per thread, so every part starts on a block boundary
+    let part_len = data.len().div_ceil(threads).next_multiple_of(BLOCK_LEN);
+    std::thread::scope(|s| {
+        for (t, part) in data.chunks_mut(part_len).enumerate() {
+            s.spawn(move || xor_keystream(cipher, layout, iv, t * part_len / BLOCK_LEN, part));
+        }
+    });
+    Ok(())
+}
+
+// Encryption step (i) for ECB: C_i = Enc[k, M_i], no chaining at all,
+// so equal plaintext blocks give equal ciphertext blocks
+fn ecb_step_enc<C: BlockCipher128 + ?Sized>(cipher: &C, block: &[u8]) -> Vec<u8>
+{
+    let mut d = AesBlock::clone_from_slice(block);
+    cipher.encrypt(&mut d);
+    d.to_vec()
+}
+
+// no nonce: the ciphertext is just the padded plaintext blocks
+pub fn encrypt_ecb<C: BlockCipher128 + ?Sized>(cipher: &C, plaintext: &[u8]) -> Vec<u8>
+{
+    padding::pad(plaintext)
+        .chunks(BLOCK_LEN)
+        .flat_map(|block| ecb_step_enc(cipher, block))
+        .collect()
+}
+
+pub fn decrypt_ecb<C: BlockCipher128 + ?Sized>(cipher: &C, ciphertext: &[u8]) -> Result<Vec<u8>, DecryptError>
+{
+    if ciphertext.is_empty() || !ciphertext.len().is_multiple_of(BLOCK_LEN) {
+        return Err(DecryptError::BadLength(ciphertext.len()));
+    }
+
+    let plaintext: Vec<u8> = ciphertext.chunks(BLOCK_LEN)
+                                       .flat_map(|block| {
+                                           let mut d = AesBlock::clone_from_slice(block);
+                                           cipher.decrypt(&mut d);
+                                           d.to_vec()
+                                       })
+                                       .collect();
+
+    padding::unpad(plaintext)
+}
+
+// OFB: the keystream is O_i = Enc[k, O_(i-1)] with O_0 = IV,
+// and encryption and decryption are the same xor
+fn ofb<C: BlockCipher128 + ?Sized>(cipher: &C, iv: &[u8], data: &[u8]) -> Vec<u8>
+{
+    data.chunks(BLOCK_LEN)
+        .scan(AesBlock::clone_from_slice(iv), |o, block| {
+            cipher.encrypt(o);
+            Some(xor(o, block))
+        })
+        .flatten()
+        .collect()
+}
+
+// nonce is the first block of ciphertext
+pub fn decrypt_ofb<C: BlockCipher128 + ?Sized>(cipher: &C, ciphertext: &[u8]) -> Result<Vec<u8>, DecryptError>
+{
+    let (iv, body) = split_iv(ciphertext)?;
+    Ok(ofb(cipher, iv, body))
+}
+
+// nonce is the first block of ciphertext
+// if the provided one is None, a new one is created
+pub fn encrypt_ofb<C: BlockCipher128 + ?Sized>(cipher: &C, plaintext: &[u8], nonce: Option<&[u8]>) -> Result<Vec<u8>, EncryptError>
+{
+    let mut ciphertext = iv_or_random(nonce)?;
+
+    let body = ofb(cipher, &ciphertext, plaintext);
+    ciphertext.extend_from_slice(&body);
+    Ok(ciphertext)
+}
+
+// Encryption/Decryption step (i) for CFB-128:
+// given the previous ciphertext block C_(i-1) and the input block X_i
+// return X_i ^ Enc[k, C_(i-1)]
+fn cfb_step<C: BlockCipher128 + ?Sized>(cipher: &C, prev_block: &[u8], block: &[u8]) -> Vec<u8>
+{
+    let mut d = AesBlock::clone_from_slice(prev_block);
+    cipher.encrypt(&mut d);
+    xor(&d, block)
+}
+
+// nonce is the first block of ciphertext
+// CFB-128: M_i = C_i ^ Enc[k, C_(i-1)], the last block may be partial
+pub fn decrypt_cfb<C: BlockCipher128 + ?Sized>(cipher: &C, ciphertext: &[u8]) -> Result<Vec<u8>, DecryptError>
+{
+    split_iv(ciphertext)?;
+
+    // a partial last block is only ever the second of a window
+    let blocks: Vec<&[u8]> = ciphertext.chunks(BLOCK_LEN).collect();
+    Ok(blocks.windows(2)
+             .flat_map(|b| cfb_step(cipher, b[0], b[1]))
+             .collect())
+}
+
+// nonce is the first block of ciphertext
+// if the provided one is None, a new one is created
+// CFB-128: C_i = M_i ^ Enc[k, C_(i-1)]
+pub fn encrypt_cfb<C: BlockCipher128 + ?Sized>(cipher: &C, plaintext: &[u8], nonce: Option<&[u8]>) -> Result<Vec<u8>, EncryptError>
+{
+    let mut ciphertext = iv_or_random(nonce)?;
+
+    for (i, block) in plaintext.chunks(BLOCK_LEN).enumerate() {
+        let next_ct = cfb_step(cipher, &ciphertext[i * BLOCK_LEN..(i + 1) * BLOCK_LEN], block);
+        ciphertext.extend_from_slice(&next_ct);
+    }
+    Ok(ciphertext)
+}
+
+// CFB-8: one byte at a time, the cipher input being the last 16 bytes of
+// IV || ciphertext. Sixteen times slower than CFB-128, but a lost or
+// corrupted byte only garbles the next 16 bytes.
+fn cfb8<C: BlockCipher128 + ?Sized>(cipher: &C, iv: &[u8], data: &[u8], encrypting: bool) -> Vec<u8>
+{
+    let mut register = AesBlock::clone_from_slice(iv);
+    data.iter().map(|&b| {
+        let mut d = register;
+        cipher.encrypt(&mut d);
+        let out = b ^ d[0];
+        register.copy_within(1.., 0);
+        register[BLOCK_LEN - 1] = if encrypting { out } else { b };
+        out
+    }).collect()
+}
+
+// nonce is the first block of ciphertext
+pub fn decrypt_cfb8<C: BlockCipher128 + ?Sized>(cipher: &C, ciphertext: &[u8]) -> Result<Vec<u8>, DecryptError>
+{
+    let (iv, body) = split_iv(ciphertext)?;
+    Ok(cfb8(cipher, iv, body, false))
+}
+
+// nonce is the first block of ciphertext
+// if the provided one is None, a new one is created
+pub fn encrypt_cfb8<C: BlockCipher128 + ?Sized>(cipher: &C, plaintext: &[u8], nonce: Option<&[u8]>) -> Result<Vec<u8>, EncryptError>
+{
+    let mut ciphertext = iv_or_random(nonce)?;
+
+    let body = cfb8(cipher, &ciphertext, plaintext, true);
+    ciphertext.extend_from_slice(&body);
+    Ok(ciphertext)
+}
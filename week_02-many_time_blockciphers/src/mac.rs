@@ -0,0 +1,82 @@
+// Block cipher MACs built on the CBC encryption step.
+//
+// Raw CBC-MAC (zero IV, last ciphertext block as tag) is only secure for
+// messages of one fixed length: knowing the tag t of a one-block message m,
+// the tag of m || (m' ^ t) is the tag of m', since the chaining value after
+// the first block is t and cancels out. CMAC (NIST SP 800-38B, RFC 4493)
+// fixes this by xoring the last block with one of two secret subkeys,
+// depending on whether it had to be padded.
+
+use crate::{cbc_step_enc, ct_eq, BlockCipher128, BLOCK_LEN};
+
+pub const TAG_LEN: usize = BLOCK_LEN;
+
+// x^128 = x^7 + x^2 + x + 1
+const RB: u128 = 0x87;
+
+// L * x in GF(2^128), big endian this time
+pub(crate) fn dbl(l: u128) -> u128
+{
+    (l << 1) ^ (RB & (l >> 127).wrapping_neg())
+}
+
+// CBC encryption of whole blocks with a zero IV, keeping the last block
+fn cbc_chain<C: BlockCipher128 + ?Sized>(cipher: &C, blocks: &[u8]) -> [u8; TAG_LEN]
+{
+    blocks.chunks(BLOCK_LEN)
+          .fold(vec![0u8; BLOCK_LEN], |prev, block| cbc_step_enc(cipher, &prev, block))
+          .try_into()
+          .unwrap()
+}
+
+// Raw CBC-MAC, the last block zero padded. Only use it for messages all of
+// the same length, see above.
+pub fn cbc_mac<C: BlockCipher128 + ?Sized>(cipher: &C, message: &[u8]) -> [u8; TAG_LEN]
+{
+    let mut padded = message.to_vec();
+    padded.resize(message.len().div_ceil(BLOCK_LEN).max(1) * BLOCK_LEN, 0);
+    cbc_chain(cipher, &padded)
+}
+
+pub fn verify_cbc_mac<C: BlockCipher128 + ?Sized>(cipher: &C, message: &[u8], tag: &[u8]) -> bool
+{
+    ct_eq(&cbc_mac(cipher, message), tag)
+}
+
+// K1 and K2: L = Enc[k, 0], K1 = L * x, K2 = L * x^2
+fn subkeys<C: BlockCipher128 + ?Sized>(cipher: &C) -> (u128, u128)
+{
+    let l = u128::from_be_bytes(cbc_chain(cipher, &[0u8; BLOCK_LEN]));
+    let k1 = dbl(l);
+    (k1, dbl(k1))
+}
+
+pub fn cmac<C: BlockCipher128 + ?Sized>(cipher: &C, message: &[u8]) -> [u8; TAG_LEN]
+{
+    let (k1, k2) = subkeys(cipher);
+
+    // a complete last block is xored with K1; a partial (or missing) one
+    // is padded with 10...0 and xored with K2
+    let complete = !message.is_empty() && message.len().is_multiple_of(BLOCK_LEN);
+    let last_start = if complete { message.len() - BLOCK_LEN } else { message.len() / BLOCK_LEN * BLOCK_LEN };
+    let (head, last) = message.split_at(last_start);
+
+    let mut last_block = [0u8; BLOCK_LEN];
+    last_block[..last.len()].copy_from_slice(last);
+    let subkey = if complete {
+        k1
+    } else {
+        last_block[last.len()] = 0x80;
+        k2
+    };
+    let last_block = (u128::from_be_bytes(last_block) ^ subkey).to_be_bytes();
+
+    let mut blocks = head.to_vec();
+    blocks.extend_from_slice(&last_block);
+    cbc_chain(cipher, &blocks)
+}
+
+pub fn verify_cmac<C: BlockCipher128 + ?Sized>(cipher: &C, message: &[u8], tag: &[u8]) -> bool
+{
+    ct_eq(&cmac(cipher, message), tag)
+}
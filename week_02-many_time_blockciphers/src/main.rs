@@ -1,174 +1,90 @@
-use std::iter::repeat;
-
-use aes::Aes128;
-use aes::cipher::{BlockEncrypt, BlockDecrypt, KeyInit};
-use rand::RngCore;
-use rand::rngs::OsRng;
-
-
-//use rand_core::OsRng;
-
-const BLOCK_LEN: usize = 16;
-type AesBlock = aes::Block;
-
-// Create random initial value (nonce)
-fn get_rand_iv() -> Vec<u8>
-{
-    let mut csprng = OsRng;
-    let mut iv = vec![0u8; BLOCK_LEN];
-    csprng.fill_bytes(&mut iv);
-    iv
-}
-
-// Given an AES block x_a and a slice x_b, return a Vec with the xor of the two
-fn xor(x_a: &AesBlock, x_b: &[u8]) -> Vec<u8>
-{
-    x_a.iter().zip(x_b).map(|(a, b)| a^b).collect()
-}
-
-// Decryption step (i) for CBC:
-// given two consecutive blocks of ciphertext, C_(i-1) and C_i,
-// return M_i = C_(i-1) ^ Dec[k, C_i]
-fn cbc_step_dec(cipher: &Aes128, prev_block: &[u8], curr_block: &[u8]) -> Vec<u8>
-{
-    let mut d = AesBlock::clone_from_slice(curr_block);
-    cipher.decrypt_block(&mut d);
-    xor(&d, prev_block)
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process;
+
+use week_02_multi_time_blockciphers::{decrypt_cbc, decrypt_ctr, encrypt_cbc, encrypt_ctr, gcm, padding, BLOCK_LEN};
+use week_02_multi_time_blockciphers::{decrypt_cfb, decrypt_cfb8, decrypt_ecb, decrypt_ofb};
+use week_02_multi_time_blockciphers::{encrypt_cfb, encrypt_cfb8, encrypt_ecb, encrypt_ofb};
+use week_02_multi_time_blockciphers::{decrypt_gcm, encrypt_gcm};
+use week_02_multi_time_blockciphers::{decrypt_cbc_cs3, encrypt_cbc_cs3};
+use week_02_multi_time_blockciphers::cts::PlaintextTooShort;
+use week_02_multi_time_blockciphers::xts::Xts;
+use week_02_multi_time_blockciphers::siv::{Siv, TAG_LEN};
+use week_02_multi_time_blockciphers::mac::{cbc_mac, cmac, verify_cbc_mac, verify_cmac};
+use week_02_multi_time_blockciphers::stream::{CbcDecryptor, CbcEncryptor, CtrDecryptor, CtrEncryptor};
+use week_02_multi_time_blockciphers::etm::{self, decrypt_etm, encrypt_etm, EtmKeys};
+use week_02_multi_time_blockciphers::{Aes, BlockCipher128, DecryptError, EncryptError, InvalidKeyLength};
+use week_02_multi_time_blockciphers::container::{self, Algorithm, ContainerError, Header, Kdf, Secret};
+use week_02_multi_time_blockciphers::{apply_keystream, decrypt_ctr_with, encrypt_ctr_with, CounterLayout};
+
+mod cli;
+
+use cli::{Command, Options};
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug)]
+enum AesType {
+    CBC,
+    CTR,
+    OFB,
+    CFB8,
+    CFB128,
+    ECB,
 }
 
-// nonce is the first block of ciphertext
-fn decrypt_cbc(key_bytes: &[u8; BLOCK_LEN], ciphertext: &Vec<u8>) -> Result<Vec<u8>, String>
-{
-    if ciphertext.len() % BLOCK_LEN != 0 {
-        return Err(format!("Invalid cyphertext length ({})", ciphertext.len()));
+fn encrypt(cipher: &Aes, plaintext: &[u8], enc_type: AesType, nonce: Option<&[u8]>) -> Result<Vec<u8>, EncryptError> {
+    match enc_type {
+        AesType::CBC => { Ok(encrypt_cbc(cipher, plaintext, nonce)) }
+        AesType::CTR => { encrypt_ctr_with(cipher, CounterLayout::Full128, plaintext, nonce) }
+        AesType::OFB => { encrypt_ofb(cipher, plaintext, nonce) }
+        AesType::CFB8 => { encrypt_cfb8(cipher, plaintext, nonce) }
+        AesType::CFB128 => { encrypt_cfb(cipher, plaintext, nonce) }
+        AesType::ECB => { Ok(encrypt_ecb(cipher, plaintext)) }
     }
-
-    let key = AesBlock::from(*key_bytes);
-    let cipher = Aes128::new(&key);
-
-    let mut plaintext: Vec<u8> = ciphertext.chunks(BLOCK_LEN)
-                                           .collect::<Vec<_>>()
-                                           .windows(2)
-                                           .map(|b| { cbc_step_dec(&cipher, b[0], b[1]) })
-                                           .flatten().collect();
-
-    let padding = *plaintext.last().unwrap() as usize;
-    plaintext.truncate(plaintext.len() - padding);
-    Ok(plaintext)
-}
-
-// Encryption step (i) for CBC:
-// given the block of ciphertext, C_(i-1) and of plaintext M_i,
-// return C_i = Enc[k, C_(i-1) ^ M_i]
-fn cbc_step_enc(cipher: &Aes128, prev_block: &[u8], curr_block: &[u8]) -> Vec<u8>
-{
-    let x = xor(&AesBlock::from_slice(prev_block), curr_block);
-    let mut d = AesBlock::clone_from_slice(x.as_slice());
-    cipher.encrypt_block(&mut d);
-    d.to_vec()
-}
-
-// nonce is the first block of ciphertext
-// if the provided one is None, a new one is created
-fn encrypt_cbc(key_bytes: &[u8; BLOCK_LEN], plaintext: &Vec<u8>, nonce: Option<&Vec<u8>>) -> Vec<u8>
-{
-    let mut ciphertext= match nonce {
-        None => { get_rand_iv() }
-        Some(x) => { x.clone() }
-    };
-
-    let key = AesBlock::from(*key_bytes);
-    let cipher = Aes128::new(&key);
-
-    let padding = BLOCK_LEN - plaintext.len() % BLOCK_LEN;
-
-    plaintext.iter().cloned().chain(repeat(padding as u8).take(padding))
-                    .collect::<Vec<_>>()
-                    .chunks(BLOCK_LEN)
-                    .scan(ciphertext.clone(), |ct, pt_block| {
-                        let next_ct = cbc_step_enc(&cipher, ct, pt_block);
-                        *ct = next_ct.to_vec();
-                        Some(next_ct)
-                    }).for_each(|b| ciphertext.extend_from_slice(&b));
-
-    ciphertext
 }
 
-// Encryption/Decryption step (i) for CTR:
-// given nonce iv and ciphertext block C_i
-// return M_i = Enc[k, iv] ^ C_i
-fn ctr_step(cipher: &Aes128, iv: u128, ct_block: &[u8]) -> Vec<u8>
-{
-    let mut pad = AesBlock::from(iv.to_be_bytes());
-    cipher.encrypt_block(&mut pad);
-    xor(&pad, &ct_block)
+fn decrypt(cipher: &Aes, ciphertext: &[u8], dec_type: AesType) -> Result<Vec<u8>, DecryptError> {
+    match dec_type {
+        AesType::CBC => { decrypt_cbc(cipher, ciphertext) }
+        AesType::CTR => { decrypt_ctr(cipher, ciphertext) }
+        AesType::OFB => { decrypt_ofb(cipher, ciphertext) }
+        AesType::CFB8 => { decrypt_cfb8(cipher, ciphertext) }
+        AesType::CFB128 => { decrypt_cfb(cipher, ciphertext) }
+        AesType::ECB => { decrypt_ecb(cipher, ciphertext) }
+    }
 }
 
-// nonce is the first block of ciphertext
-fn decrypt_ctr(key_bytes: &[u8; BLOCK_LEN], ciphertext: &Vec<u8>) -> Vec<u8>
-{
-    let key = AesBlock::from(*key_bytes);
-    let cipher = Aes128::new(&key);
-
-    let mut iter = ciphertext.chunks(BLOCK_LEN);
-    let iv = u128::from_be_bytes(iter.next().unwrap().try_into().unwrap());
-
-    iter.enumerate()
-        .flat_map(|(i, block)| {
-            ctr_step(&cipher, iv + i as u128, block)
-        }).collect()
+fn test_decrypt(cipher: &Aes, ciphertext: &[u8], dec_type: AesType, idx: u8) {
+    match decrypt(cipher, ciphertext, dec_type) {
+        Ok(plaintext) => { println!(
+            "plaintext-{} is: {}", idx,
+            String::from_utf8_lossy(plaintext.as_slice()))
+        }
+        Err(e) => { println!(
+            "error decrypting ciphertext-{}: {}", idx, e)
+        }
+    }
 }
 
-// nonce is the first block of ciphertext
-// if the provided one is None, a new one is created
-fn encrypt_ctr(key_bytes: &[u8; BLOCK_LEN], plaintext: &Vec<u8>, nonce: Option<&Vec<u8>>) -> Vec<u8>
-{
-    let mut ciphertext= match nonce {
-        None => { get_rand_iv() }
-        Some(x) => { x.clone() }
-    };
-
-    let key = AesBlock::from(*key_bytes);
-    let cipher = Aes128::new(&key);
-
-    let iter = plaintext.chunks(BLOCK_LEN);
-    let iv = u128::from_be_bytes(ciphertext.as_slice().try_into().unwrap());
-
-    ciphertext.append(&mut iter.enumerate()
-        .flat_map(|(i, block)| {
-            ctr_step(&cipher, iv + i as u128, block)
-        }).collect());
-    ciphertext
-}
+// Not a cipher: Enc[k, 0] is 1 in GCM's bit order, so that J0 for a
+// 16-byte nonce is the nonce xor its bit length, and can be put right
+// before the 32-bit counter wraps
+struct GcmOne;
 
-enum AesType {
-    CBC,
-    CTR,
-}
+impl BlockCipher128 for GcmOne {
+    fn encrypt(&self, block: &mut aes::Block) {
+        block[0] ^= 0x80;
+    }
 
-fn test_decrypt(key_bytes: &[u8; BLOCK_LEN], ciphertext: &Vec<u8>, dec_type: AesType, idx: u8) {
-    match dec_type {
-        AesType::CBC => {
-            match decrypt_cbc(key_bytes, ciphertext) {
-                Ok(plaintext) => { println!(
-                    "plaintext-{} is: {}", idx,
-                    String::from_utf8_lossy(&plaintext.as_slice()))
-                }
-                Err(e) => { println!(
-                    "error decrypting ciphertext-{}: {}", idx, e)
-                }
-            }
-        }
-        AesType::CTR => {
-            let plaintext = decrypt_ctr(key_bytes, ciphertext);
-            println!("plaintext-{} is: {}", idx, String::from_utf8_lossy(&plaintext.as_slice()));
-        }
+    fn decrypt(&self, block: &mut aes::Block) {
+        block[0] ^= 0x80;
     }
 }
 
-fn main() {
-    let cbc_key: [u8; BLOCK_LEN] = hex::decode("140b41b22a29beb4061bda66b6747e14").unwrap().try_into().unwrap();
-    let ctr_key: [u8; BLOCK_LEN] = hex::decode("36f18357be4dbd77f050515c73fcf9f2").unwrap().try_into().unwrap();
+// Known-answer tests of every mode
+fn self_test() {
+    let cbc_key = Aes::new(&hex::decode("140b41b22a29beb4061bda66b6747e14").unwrap()).unwrap();
+    let ctr_key = Aes::new(&hex::decode("36f18357be4dbd77f050515c73fcf9f2").unwrap()).unwrap();
     let ciphertext_1 = hex::decode("4ca00ff4c898d61e1edbf1800618fb28\
                                                   28a226d160dad07883d04e008a7897ee\
                                                   2e4b7465d5290d0c0e6c6822236e1daa\
@@ -192,21 +108,629 @@ fn main() {
 
     // test encryption too
     let nonce: Vec<u8> = hex::decode("4ca00ff4c898d61e1edbf1800618fb28").unwrap();
-    let mut pt = String::from("Basic CBC mode encryption needs padding.").into_bytes();
-    assert_eq!(ciphertext_1, encrypt_cbc(&cbc_key, &mut pt, Some(&nonce)));
-    assert_ne!(ciphertext_1, encrypt_cbc(&cbc_key, &mut pt, None));
+    let pt = String::from("Basic CBC mode encryption needs padding.").into_bytes();
+    assert_eq!(ciphertext_1, encrypt_cbc(&cbc_key, &pt, Some(&nonce)));
+    assert_ne!(ciphertext_1, encrypt_cbc(&cbc_key, &pt, None));
 
     let nonce: Vec<u8> = hex::decode("5b68629feb8606f9a6667670b75b38a5").unwrap();
-    let mut pt = String::from("Our implementation uses rand. IV").into_bytes();
-    assert_eq!(ciphertext_2, encrypt_cbc(&cbc_key, &mut pt, Some(&nonce)));
+    let pt = String::from("Our implementation uses rand. IV").into_bytes();
+    assert_eq!(ciphertext_2, encrypt_cbc(&cbc_key, &pt, Some(&nonce)));
 
     let nonce: Vec<u8> = hex::decode("69dda8455c7dd4254bf353b773304eec").unwrap();
-    let mut pt = String::from("CTR mode lets you build a stream cipher from a block cipher.").into_bytes();
-    assert_eq!(ciphertext_3, encrypt_ctr(&ctr_key, &mut pt, Some(&nonce)));
-    assert_ne!(ciphertext_3, encrypt_ctr(&ctr_key, &mut pt, None));
+    let pt = String::from("CTR mode lets you build a stream cipher from a block cipher.").into_bytes();
+    assert_eq!(ciphertext_3, encrypt_ctr(&ctr_key, &pt, Some(&nonce)));
+    assert_ne!(ciphertext_3, encrypt_ctr(&ctr_key, &pt, None));
 
     let nonce: Vec<u8> = hex::decode("770b80259ec33beb2561358a9f2dc617").unwrap();
-    let mut pt = String::from("Always avoid the two time pad!").into_bytes();
-    assert_eq!(ciphertext_4, encrypt_ctr(&ctr_key, &mut pt, Some(&nonce)));
+    let pt = String::from("Always avoid the two time pad!").into_bytes();
+    assert_eq!(ciphertext_4, encrypt_ctr(&ctr_key, &pt, Some(&nonce)));
+
+    // malformed ciphertexts are rejected, not decrypted to garbage
+    assert_eq!(decrypt_cbc(&cbc_key, &[]), Err(DecryptError::MissingIv));
+    assert_eq!(decrypt_cbc(&cbc_key, &ciphertext_1[..8]), Err(DecryptError::MissingIv));
+    assert_eq!(decrypt_cbc(&cbc_key, &ciphertext_1[..BLOCK_LEN]), Err(DecryptError::BadLength(16)));
+    assert_eq!(decrypt_cbc(&cbc_key, &ciphertext_1[..40]), Err(DecryptError::BadLength(40)));
+    assert_eq!(decrypt_ctr(&ctr_key, &ciphertext_3[..4]), Err(DecryptError::MissingIv));
+    assert_eq!(decrypt_ctr(&ctr_key, &ciphertext_3[..BLOCK_LEN]), Ok(Vec::new()));
+
+    // dropping the last block leaves message bytes where the padding should be
+    assert_eq!(decrypt_cbc(&cbc_key, &ciphertext_1[..48]), Err(DecryptError::InvalidPadding));
+
+    assert_eq!(padding::pad(b""), vec![16u8; 16]);
+    assert_eq!(padding::pad(b"YELLOW SUBMARINE\x01\x02"), b"YELLOW SUBMARINE\x01\x02\x0e\x0e\x0e\x0e\x0e\x0e\x0e\x0e\x0e\x0e\x0e\x0e\x0e\x0e".to_vec());
+    assert_eq!(padding::unpad(b"ICE ICE BABY\x04\x04\x04\x04".to_vec()), Ok(b"ICE ICE BABY".to_vec()));
+    assert_eq!(padding::unpad(vec![16u8; 16]), Ok(Vec::new()));
+    assert_eq!(padding::unpad(b"ICE ICE BABY\x05\x05\x05\x05".to_vec()), Err(DecryptError::InvalidPadding));
+    assert_eq!(padding::unpad(b"ICE ICE BABY\x01\x02\x03\x04".to_vec()), Err(DecryptError::InvalidPadding));
+    assert_eq!(padding::unpad(b"ICE ICE BABY\x04\x04\x04\x00".to_vec()), Err(DecryptError::InvalidPadding));
+    assert_eq!(padding::unpad(b"ICE ICE BABY\x04\x04\x04\x11".to_vec()), Err(DecryptError::InvalidPadding));
+    assert_eq!(padding::unpad(b"ICE ICE BABY\x04\x04\x04".to_vec()), Err(DecryptError::BadLength(15)));
+
+    // AES-192 and AES-256, NIST SP 800-38A F.2 (CBC) and F.5 (CTR) vectors.
+    // The CBC vectors have no padding, which adds one block at the end here.
+    let pt = hex::decode("6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+                          30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710").unwrap();
+    let cbc_iv = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+    let ctr_iv = hex::decode("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff").unwrap();
+    let vectors = [
+        ("8e73b0f7da0e6452c810f32b809079e562f8ead2522c6b7b",
+         "4f021db243bc633d7178183a9fa071e8b4d9ada9ad7dedf4e5e738763f69145a\
+          571b242012fb7ae07fa9baac3df102e008b0e27988598881d920a9e64f5615cd",
+         "1abc932417521ca24f2b0459fe7e6e0b090339ec0aa6faefd5ccc2c6f4ce8e94\
+          1e36b26bd1ebc670d1bd1d665620abf74f78a7f6d29809585a97daec58c6b050"),
+        ("603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4",
+         "f58c4c04d6e5f1ba779eabfb5f7bfbd69cfc4e967edb808d679f777bc6702c7d\
+          39f23369a9d9bacfa530e26304231461b2eb05e2c39be9fcda6c19078c6a9d1b",
+         "601ec313775789a5b7a7f504bbf3d228f443e3ca4d62b59aca84e990cacaf5c5\
+          2b0930daa23de94ce87017ba2d84988ddfc9c58db67aada613c2dd08457941a6"),
+    ];
+    for (key, cbc_ct, ctr_ct) in vectors {
+        let cipher = Aes::new(&hex::decode(key).unwrap()).unwrap();
+
+        let ct = encrypt_cbc(&cipher, &pt, Some(&cbc_iv));
+        assert_eq!(ct[BLOCK_LEN..BLOCK_LEN + pt.len()], hex::decode(cbc_ct).unwrap());
+        assert_eq!(decrypt_cbc(&cipher, &ct), Ok(pt.clone()));
+
+        let ct = encrypt_ctr(&cipher, &pt, Some(&ctr_iv));
+        assert_eq!(ct[BLOCK_LEN..], hex::decode(ctr_ct).unwrap());
+        assert_eq!(decrypt_ctr(&cipher, &ct), Ok(pt.clone()));
+    }
+    assert!(matches!(Aes::new(&[0u8; 20]), Err(InvalidKeyLength(20))));
+
+    // GCM, test cases 1-6 and 13-16 of the GCM specification (the NIST vectors)
+    let p = "d9313225f88406e5a55909c5aff5269a86a7a9531534f7da2e4c303d8a318a72\
+             1c3c0c95956809532fcf0e2449a6b525b16aedf5aa0de657ba637b391aafd255";
+    let a = "feedfacedeadbeeffeedfacedeadbeefabaddad2";
+    let k = "feffe9928665731c6d6a8f9467308308";
+    let vectors = [
+        ("00000000000000000000000000000000", "000000000000000000000000", "", "", "",
+         "58e2fccefa7e3061367f1d57a4e7455a"),
+        ("00000000000000000000000000000000", "000000000000000000000000", "00000000000000000000000000000000", "",
+         "0388dace60b6a392f328c2b971b2fe78", "ab6e47d42cec13bdf53a67b21257bddf"),
+        (k, "cafebabefacedbaddecaf888", p, "",
+         "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e\
+          21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091473f5985",
+         "4d5c2af327cd64a62cf35abd2ba6fab4"),
+        (k, "cafebabefacedbaddecaf888", &p[..120], a,
+         "42831ec2217774244b7221b784d0d49ce3aa212f2c02a4e035c17e2329aca12e\
+          21d514b25466931c7d8f6a5aac84aa051ba30b396a0aac973d58e091",
+         "5bc94fbc3221a5db94fae95ae7121a47"),
+        (k, "cafebabefacedbad", &p[..120], a,
+         "61353b4c2806934a777ff51fa22a4755699b2a714fcdc6f83766e5f97b6c7423\
+          73806900e49f24b22b097544d4896b424989b5e1ebac0f07c23f4598",
+         "3612d2e79e3b0785561be14aaca2fccb"),
+        (k, "9313225df88406e555909c5aff5269aa6a7a9538534f7da1e4c303d2a318a728\
+             c3c0c95156809539fcf0e2429a6b525416aedbf5a0de6a57a637b39b", &p[..120], a,
+         "8ce24998625615b603a033aca13fb894be9112a5c3a211a8ba262a3cca7e2ca7\
+          01e4a9a4fba43c90ccdcb281d48c7c6fd62875d2aca417034c34aee5",
+         "619cc5aefffe0bfa462af43c1699d050"),
+        ("0000000000000000000000000000000000000000000000000000000000000000", "000000000000000000000000", "", "", "",
+         "530f8afbc74536b9a963b4f1c4cb738b"),
+        ("0000000000000000000000000000000000000000000000000000000000000000", "000000000000000000000000",
+         "00000000000000000000000000000000", "",
+         "cea7403d4d606b6e074ec5d3baf39d18", "d0d1c8a799996bf0265b98b5d48ab919"),
+        ("feffe9928665731c6d6a8f9467308308feffe9928665731c6d6a8f9467308308", "cafebabefacedbaddecaf888", &p[..120], a,
+         "522dc1f099567d07f47f37a32a84427d643a8cdcbfe5c0c97598a2bd2555d1aa\
+          8cb08e48590dbb3da7b08b1056828838c5f61e6393ba7a0abcc9f662",
+         "76fc6ece0f4e1768cddf8853bb2d551b"),
+    ];
+    for (key, iv, pt, aad, ct, tag) in vectors {
+        let cipher = Aes::new(&hex::decode(key).unwrap()).unwrap();
+        let (iv, pt, aad) = (hex::decode(iv).unwrap(), hex::decode(pt).unwrap(), hex::decode(aad).unwrap());
+        let (ct, tag) = (hex::decode(ct).unwrap(), hex::decode(tag).unwrap());
+
+        let (sealed, sealed_tag) = gcm::seal(&cipher, &iv, &aad, &pt).unwrap();
+        assert_eq!((&sealed, &sealed_tag[..]), (&ct, &tag[..]));
+        assert_eq!(gcm::open(&cipher, &iv, &aad, &ct, &tag), Ok(pt.clone()));
+
+        // any flipped bit, in the tag, the ciphertext or the associated data, is caught
+        let mut forged = tag.clone();
+        forged[0] ^= 1;
+        assert_eq!(gcm::open(&cipher, &iv, &aad, &ct, &forged), Err(DecryptError::InvalidTag));
+        if !ct.is_empty() {
+            let mut forged = ct.clone();
+            forged[ct.len() - 1] ^= 0x80;
+            assert_eq!(gcm::open(&cipher, &iv, &aad, &forged, &tag), Err(DecryptError::InvalidTag));
+        }
+        if !aad.is_empty() {
+            assert_eq!(gcm::open(&cipher, &iv, &aad[1..], &ct, &tag), Err(DecryptError::InvalidTag));
+        }
+    }
+
+    let msg = b"GCM tells you when the ciphertext was tampered with.";
+    let ct = encrypt_gcm(&ctr_key, msg, b"header", None).unwrap();
+    assert_eq!(decrypt_gcm(&ctr_key, &ct, b"header"), Ok(msg.to_vec()));
+    assert_eq!(decrypt_gcm(&ctr_key, &ct, b"footer"), Err(DecryptError::InvalidTag));
+    assert_eq!(decrypt_gcm(&ctr_key, &ct[..20], b"header"), Err(DecryptError::BadLength(20)));
+    assert_eq!(decrypt_gcm(&ctr_key, &ct[..8], b"header"), Err(DecryptError::MissingIv));
+    // a wrong nonce is refused, not a panic
+    assert_eq!(encrypt_gcm(&ctr_key, msg, b"", Some(&ct[..16])), Err(EncryptError::InvalidNonceLength(16)));
+    assert_eq!(encrypt_gcm(&ctr_key, msg, b"", Some(&[])), Err(EncryptError::InvalidNonceLength(0)));
+    assert_eq!(gcm::seal(&ctr_key, &[], b"", msg), Err(EncryptError::InvalidNonceLength(0)));
+    assert_eq!(encrypt_gcm(&ctr_key, msg, b"", Some(&ct[..12])).unwrap()[..12], ct[..12]);
+    // the 32-bit counter is not allowed to wrap, and no text is longer than 2^32 - 2 blocks
+    let mut nonce = [0u8; 16];
+    nonce[12..].copy_from_slice(&0xffff_ff7eu32.to_be_bytes());
+    let (ct, tag) = gcm::seal(&GcmOne, &nonce, b"", &msg[..16]).unwrap();
+    assert_eq!(gcm::open(&GcmOne, &nonce, b"", &ct, &tag), Ok(msg[..16].to_vec()));
+    assert_eq!(gcm::seal(&GcmOne, &nonce, b"", &msg[..17]), Err(EncryptError::CounterOverflow));
+    assert_eq!(gcm::open(&GcmOne, &nonce, b"", &msg[..17], &tag), Err(DecryptError::CounterOverflow));
+    nonce[15] = 0x7f;
+    assert_eq!(gcm::seal(&GcmOne, &nonce, b"", &msg[..1]), Err(EncryptError::CounterOverflow));
+    assert_eq!(gcm::seal(&GcmOne, &nonce, b"", b"").map(|(ct, _)| ct), Ok(vec![]));
+    assert_eq!(CounterLayout::Nonce96Counter32.check(2, gcm::MAX_LEN as usize), Ok(()));
+    assert!(CounterLayout::Nonce96Counter32.check(2, gcm::MAX_LEN as usize + 1).is_err());
+
+    // encrypt-then-MAC: the tag is checked before decrypting
+    let master = hex::decode("000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f").unwrap();
+    let keys = EtmKeys::derive(&master).unwrap();
+    for mode in [etm::Mode::Cbc, etm::Mode::Ctr] {
+        let ct = encrypt_etm(&keys, mode, msg, None).unwrap();
+        assert_eq!(decrypt_etm(&keys, mode, &ct), Ok(msg.to_vec()));
+        assert_eq!(encrypt_etm(&keys, mode, msg, Some(&master[..BLOCK_LEN])).unwrap()[..BLOCK_LEN], master[..BLOCK_LEN]);
+        for len in [0, 12, 20] {
+            assert_eq!(encrypt_etm(&keys, mode, msg, Some(&master[..len])), Err(EncryptError::InvalidNonceLength(len)));
+        }
+        for i in [0, BLOCK_LEN, ct.len() - 1] {
+            let mut forged = ct.clone();
+            forged[i] ^= 1;
+            assert_eq!(decrypt_etm(&keys, mode, &forged), Err(DecryptError::InvalidTag));
+        }
+        assert_eq!(decrypt_etm(&keys, mode, &ct[..40]), Err(DecryptError::BadLength(40)));
+    }
+    // a padding oracle query (tweaking the block before the last one) is
+    // stopped by the MAC before the padding is ever looked at
+    let mut forged = encrypt_etm(&keys, etm::Mode::Cbc, msg, None).unwrap();
+    let last = forged.len() - etm::TAG_LEN - BLOCK_LEN - 1;
+    forged[last] ^= 1;
+    assert_eq!(decrypt_etm(&keys, etm::Mode::Cbc, &forged), Err(DecryptError::InvalidTag));
+    assert!(matches!(EtmKeys::derive(&master[..20]), Err(InvalidKeyLength(20))));
+
+    // OFB, CFB and ECB, NIST SP 800-38A F.1 (ECB), F.3 (CFB) and F.4 (OFB)
+    // vectors for AES-128 and AES-256, same plaintext as above.
+    // ECB adds a padding block, CFB-8 vectors are 18 bytes long.
+    let aes128 = Aes::new(&hex::decode("2b7e151628aed2a6abf7158809cf4f3c").unwrap()).unwrap();
+    let aes256 = Aes::new(&hex::decode("603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4").unwrap()).unwrap();
+    let vectors = [
+        (&aes128, AesType::ECB,
+         "3ad77bb40d7a3660a89ecaf32466ef97f5d3d58503b9699de785895a96fdbaaf\
+          43b1cd7f598ece23881b00e3ed0306887b0c785e27e8ad3f8223207104725dd4"),
+        (&aes128, AesType::CFB128,
+         "3b3fd92eb72dad20333449f8e83cfb4ac8a64537a0b3a93fcde3cdad9f1ce58b\
+          26751f67a3cbb140b1808cf187a4f4dfc04b05357c5d1c0eeac4c66f9ff7f2e6"),
+        (&aes128, AesType::OFB,
+         "3b3fd92eb72dad20333449f8e83cfb4a7789508d16918f03f53c52dac54ed825\
+          9740051e9c5fecf64344f7a82260edcc304c6528f659c77866a510d9c1d6ae5e"),
+        (&aes128, AesType::CFB8, "3b79424c9c0dd436bace9e0ed4586a4f32b9"),
+        (&aes256, AesType::ECB,
+         "f3eed1bdb5d2a03c064b5a7e3db181f8591ccb10d410ed26dc5ba74a31362870\
+          b6ed21b99ca6f4f9f153e7b1beafed1d23304b7a39f9f3ff067d8d8f9e24ecc7"),
+        (&aes256, AesType::CFB128,
+         "dc7e84bfda79164b7ecd8486985d386039ffed143b28b1c832113c6331e5407b\
+          df10132415e54b92a13ed0a8267ae2f975a385741ab9cef82031623d55b1e471"),
+        (&aes256, AesType::OFB,
+         "dc7e84bfda79164b7ecd8486985d38604febdc6740d20b3ac88f6ad82a4fb08d\
+          71ab47a086e86eedf39d1c5bba97c4080126141d67f37be8538f5a8be740e484"),
+        (&aes256, AesType::CFB8, "dc1f1a8520a64db55fcc8ac554844e889700"),
+    ];
+    for (cipher, mode, expected) in vectors {
+        let expected = hex::decode(expected).unwrap();
+        let pt = &pt[..expected.len()];
+        let (iv_len, ct) = match mode {
+            AesType::ECB => { (0, encrypt(cipher, pt, mode, None).unwrap()) }
+            _ => { (BLOCK_LEN, encrypt(cipher, pt, mode, Some(&cbc_iv)).unwrap()) }
+        };
+        assert_eq!(ct[iv_len..iv_len + expected.len()], expected, "{:?}", mode);
+        assert_eq!(decrypt(cipher, &ct, mode), Ok(pt.to_vec()), "{:?}", mode);
+    }
+
+    // the stream-like modes take any length, no padding
+    let long_nonce = [7u8; 20];
+    for mode in [AesType::CTR, AesType::OFB, AesType::CFB8, AesType::CFB128] {
+        for len in [0, 1, 15, 16, 17, 33] {
+            let ct = encrypt(&ctr_key, &msg[..len], mode, None).unwrap();
+            assert_eq!(ct.len(), BLOCK_LEN + len);
+            assert_eq!(decrypt(&ctr_key, &ct, mode), Ok(msg[..len].to_vec()));
+        }
+        assert_eq!(decrypt(&ctr_key, &msg[..5], mode), Err(DecryptError::MissingIv));
+        // a nonce is one block, no more no less
+        for len in [0, 8, 15, 17, 20] {
+            assert_eq!(encrypt(&ctr_key, msg, mode, Some(&long_nonce[..len])), Err(EncryptError::InvalidNonceLength(len)));
+        }
+    }
+    assert_eq!(decrypt_ecb(&aes128, &[0u8; 20]), Err(DecryptError::BadLength(20)));
+    // ECB leaks equal blocks
+    let ct = encrypt_ecb(&aes128, &[b'A'; 2 * BLOCK_LEN]);
+    assert_eq!(ct[..BLOCK_LEN], ct[BLOCK_LEN..2 * BLOCK_LEN]);
+
+    // streaming: same output as the whole-buffer functions, whatever the
+    // sizes of the writes and reads
+    let nonce = hex::decode("69dda8455c7dd4254bf353b773304eec").unwrap();
+    let long: Vec<u8> = (0..1000u32).map(|i| (i * 7 % 251) as u8).collect();
+    for len in [0, 1, 15, 16, 17, 100, 1000] {
+        let pt = &long[..len];
+        for piece in [1, 7, 16, 33, 4096] {
+            let mut w = CbcEncryptor::new(&ctr_key, Vec::new(), Some(&nonce)).unwrap();
+            pt.chunks(piece).for_each(|c| w.write_all(c).unwrap());
+            let ct = w.finish().unwrap();
+            assert_eq!(ct, encrypt_cbc(&ctr_key, pt, Some(&nonce)));
+
+            let mut r = CbcDecryptor::new(&ctr_key, ct.as_slice()).unwrap();
+            let mut out = Vec::new();
+            let mut buf = vec![0u8; piece];
+            loop {
+                let n = r.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                out.extend_from_slice(&buf[..n]);
+            }
+            assert_eq!(out, pt);
+
+            let mut w = CtrEncryptor::new(&ctr_key, Vec::new(), Some(&nonce)).unwrap();
+            pt.chunks(piece).for_each(|c| w.write_all(c).unwrap());
+            let ct = w.finish().unwrap();
+            assert_eq!(ct, encrypt_ctr(&ctr_key, pt, Some(&nonce)));
+
+            let mut out = Vec::new();
+            CtrDecryptor::new(&ctr_key, ct.as_slice()).unwrap().read_to_end(&mut out).unwrap();
+            assert_eq!(out, pt);
+        }
+    }
+    let invalid = |ct: &[u8]| {
+        let mut out = Vec::new();
+        let err = CbcDecryptor::new(&cbc_key, ct).and_then(|mut r| r.read_to_end(&mut out)).unwrap_err();
+        err.into_inner().unwrap().downcast::<DecryptError>().map(|e| *e).unwrap()
+    };
+    assert_eq!(invalid(&ciphertext_1[..8]), DecryptError::MissingIv);
+    assert_eq!(invalid(&ciphertext_1[..BLOCK_LEN]), DecryptError::BadLength(16));
+    assert_eq!(invalid(&ciphertext_1[..40]), DecryptError::BadLength(40));
+    assert_eq!(invalid(&ciphertext_1[..48]), DecryptError::InvalidPadding);
+
+    // nothing is kept around but the current block
+    let size = 8 << 20;
+    let mut w = CtrEncryptor::new(&ctr_key, io::sink(), None).unwrap();
+    assert_eq!(io::copy(&mut io::repeat(0x42).take(size), &mut w).unwrap(), size);
+    let mut w = CbcEncryptor::new(&cbc_key, Vec::new(), None).unwrap();
+    io::copy(&mut io::repeat(0x42).take(1 << 20), &mut w).unwrap();
+    let ct = w.finish().unwrap();
+    let mut r = CbcDecryptor::new(&cbc_key, ct.as_slice()).unwrap();
+    assert_eq!(io::copy(&mut r, &mut io::sink()).unwrap(), 1 << 20);
+    // a nonce that is not one block is an error, nothing is written
+    for len in [0, 12, 20] {
+        let e = CbcEncryptor::new(&ctr_key, Vec::new(), Some(&long[..len])).err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(e.into_inner().unwrap().downcast::<EncryptError>().ok().map(|e| *e),
+                   Some(EncryptError::InvalidNonceLength(len)));
+        let e = CtrEncryptor::new(&ctr_key, Vec::new(), Some(&long[..len])).err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    // CTR counters: the full 128-bit counter wraps around to 0, the split
+    // ones refuse to carry into the nonce
+    let pt = &long[..3 * BLOCK_LEN];
+    let top = [0xffu8; BLOCK_LEN];
+    let ct = encrypt_ctr(&aes128, pt, Some(&top));
+    let mut zero_block = encrypt_ecb(&aes128, &[0u8; BLOCK_LEN]);
+    zero_block.truncate(BLOCK_LEN);
+    let ks: Vec<u8> = ct[2 * BLOCK_LEN..3 * BLOCK_LEN].iter().zip(&pt[BLOCK_LEN..]).map(|(c, p)| c ^ p).collect();
+    assert_eq!(ks, zero_block);
+    assert_eq!(decrypt_ctr(&aes128, &ct), Ok(pt.to_vec()));
+
+    let layouts = [
+        (CounterLayout::Nonce96Counter32, "0102030405060708090a0b0cfffffffe"),
+        (CounterLayout::Nonce64Counter64, "0102030405060708fffffffffffffffe"),
+    ];
+    for (layout, iv) in layouts {
+        let iv = hex::decode(iv).unwrap();
+        // two blocks fit, the third one would wrap
+        let ct = encrypt_ctr_with(&aes128, layout, &pt[..2 * BLOCK_LEN], Some(&iv)).unwrap();
+        assert_eq!(ct, encrypt_ctr(&aes128, &pt[..2 * BLOCK_LEN], Some(&iv)));
+        assert_eq!(decrypt_ctr_with(&aes128, layout, &ct), Ok(pt[..2 * BLOCK_LEN].to_vec()));
+        assert_eq!(encrypt_ctr_with(&aes128, layout, &pt[..2 * BLOCK_LEN + 1], Some(&iv)),
+                   Err(EncryptError::CounterOverflow));
+        assert_eq!(encrypt_ctr_with(&aes128, layout, pt, Some(&iv[..12])), Err(EncryptError::InvalidNonceLength(12)));
+        assert_eq!(encrypt_ctr_with(&aes128, layout, pt, Some(&[])), Err(EncryptError::InvalidNonceLength(0)));
+        let ct = encrypt_ctr(&aes128, pt, Some(&iv));
+        assert_eq!(decrypt_ctr_with(&aes128, layout, &ct), Err(DecryptError::CounterOverflow));
+    }
+    let iv = hex::decode("0102030405060708090a0b0c00000000").unwrap();
+    assert_eq!(CounterLayout::Nonce96Counter32.nth(u128::from_be_bytes(iv.try_into().unwrap()), 5),
+               Ok(0x0102030405060708090a0b0c00000005));
+
+    // in-place CTR, batched and threaded, is the same keystream
+    let big: Vec<u8> = (0..300_001u32).map(|i| (i % 253) as u8).collect();
+    for len in [0, 1, 17, 1000, big.len()] {
+        let ct = encrypt_ctr(&aes256, &big[..len], Some(&nonce));
+        for threads in [1, 4] {
+            let mut data = big[..len].to_vec();
+            apply_keystream(&aes256, CounterLayout::Full128, &nonce, &mut data, threads).unwrap();
+            assert_eq!(data, ct[BLOCK_LEN..]);
+            apply_keystream(&aes256, CounterLayout::Full128, &nonce, &mut data, threads).unwrap();
+            assert_eq!(data, big[..len]);
+        }
+    }
+    let iv = hex::decode("0102030405060708090a0b0cfffffffe").unwrap();
+    let mut data = big[..2 * BLOCK_LEN + 1].to_vec();
+    assert_eq!(apply_keystream(&aes256, CounterLayout::Nonce96Counter32, &iv, &mut data, 1),
+               Err(EncryptError::CounterOverflow));
+    assert_eq!(apply_keystream(&aes256, CounterLayout::Full128, &iv[..12], &mut data, 1),
+               Err(EncryptError::InvalidNonceLength(12)));
+    assert_eq!(apply_keystream(&aes256, CounterLayout::Full128, &[], &mut data, 4),
+               Err(EncryptError::InvalidNonceLength(0)));
+    assert_eq!(data, big[..2 * BLOCK_LEN + 1]);
+
+    // CBC-CS3, RFC 3962 vectors (zero IV)
+    let chicken = Aes::new(b"chicken teriyaki").unwrap();
+    let order = b"I would like the General Gau's Chicken, please, and wonton soup.";
+    let vectors = [
+        (17, "c6353568f2bf8cb4d8a580362da7ff7f97"),
+        (31, "fc00783e0efdb2c1d445d4c8eff7ed2297687268d6ecccc0c07b25e25ecfe5"),
+        (32, "39312523a78662d5be7fcbcc98ebf5a897687268d6ecccc0c07b25e25ecfe584"),
+        (47, "97687268d6ecccc0c07b25e25ecfe584b3fffd940c16a18c1b5549d2f838029e\
+              39312523a78662d5be7fcbcc98ebf5"),
+        (48, "97687268d6ecccc0c07b25e25ecfe5849dad8bbb96c4cdc03bc103e1a194bbd8\
+              39312523a78662d5be7fcbcc98ebf5a8"),
+        (64, "97687268d6ecccc0c07b25e25ecfe58439312523a78662d5be7fcbcc98ebf5a8\
+              4807efe836ee89a526730dbc2f7bc8409dad8bbb96c4cdc03bc103e1a194bbd8"),
+    ];
+    let zero_iv = [0u8; BLOCK_LEN];
+    for (len, expected) in vectors {
+        let ct = encrypt_cbc_cs3(&chicken, &order[..len], Some(&zero_iv)).unwrap();
+        assert_eq!(ct.len(), BLOCK_LEN + len);
+        assert_eq!(ct[BLOCK_LEN..], hex::decode(expected).unwrap());
+        assert_eq!(decrypt_cbc_cs3(&chicken, &ct), Ok(order[..len].to_vec()));
+    }
+    for len in 16..100 {
+        let ct = encrypt_cbc_cs3(&aes256, &long[..len], None).unwrap();
+        assert_eq!(decrypt_cbc_cs3(&aes256, &ct), Ok(long[..len].to_vec()));
+    }
+    assert_eq!(encrypt_cbc_cs3(&aes256, &long[..15], None), Err(EncryptError::PlaintextTooShort(15)));
+    for len in [0, 12, 20] {
+        assert_eq!(encrypt_cbc_cs3(&aes256, &long[..32], Some(&long[..len])), Err(EncryptError::InvalidNonceLength(len)));
+    }
+    assert_eq!(decrypt_cbc_cs3(&aes256, &long[..31]), Err(DecryptError::BadLength(31)));
+
+    // XTS-AES-128, IEEE 1619 annex B vectors 1-3 and 15-18 (ciphertext stealing)
+    let steal = "fffefdfcfbfaf9f8f7f6f5f4f3f2f1f0bfbebdbcbbbab9b8b7b6b5b4b3b2b1b0";
+    let vectors = [
+        ("0000000000000000000000000000000000000000000000000000000000000000", 0,
+         "0000000000000000000000000000000000000000000000000000000000000000",
+         "917cf69ebd68b2ec9b9fe9a3eadda692cd43d2f59598ed858c02c2652fbf922e"),
+        ("1111111111111111111111111111111122222222222222222222222222222222", 0x3333333333,
+         "4444444444444444444444444444444444444444444444444444444444444444",
+         "c454185e6a16936e39334038acef838bfb186fff7480adc4289382ecd6d394f0"),
+        ("fffefdfcfbfaf9f8f7f6f5f4f3f2f1f022222222222222222222222222222222", 0x3333333333,
+         "4444444444444444444444444444444444444444444444444444444444444444",
+         "af85336b597afc1a900b2eb21ec949d292df4c047e0b21532186a5971a227a89"),
+        (steal, 0x123456789a, "000102030405060708090a0b0c0d0e0f10", "6c1625db4671522d3d7599601de7ca09ed"),
+        (steal, 0x123456789a, "000102030405060708090a0b0c0d0e0f1011", "d069444b7a7e0cab09e24447d24deb1fedbf"),
+        (steal, 0x123456789a, "000102030405060708090a0b0c0d0e0f101112", "e5df1351c0544ba1350b3363cd8ef4beedbf9d"),
+        (steal, 0x123456789a, "000102030405060708090a0b0c0d0e0f10111213", "9d84c813f719aa2c7be3f66171c7c5c2edbf9dac"),
+    ];
+    for (key, sector, pt, ct) in vectors {
+        let xts = Xts::new(&hex::decode(key).unwrap()).unwrap();
+        let (pt, ct) = (hex::decode(pt).unwrap(), hex::decode(ct).unwrap());
+        assert_eq!(xts.encrypt_sector(sector, &pt), Ok(ct.clone()));
+        assert_eq!(xts.decrypt_sector(sector, &ct), Ok(pt));
+    }
+    let xts = Xts::new(&long[..64]).unwrap();
+    for len in [16, 17, 31, 32, 33, 512, 999] {
+        let ct = xts.encrypt_sector(7, &long[..len]).unwrap();
+        assert_eq!(ct.len(), len);
+        assert_eq!(xts.decrypt_sector(7, &ct), Ok(long[..len].to_vec()));
+        // same data, other sector: unrelated ciphertext
+        assert_ne!(xts.encrypt_sector(8, &long[..len]).unwrap()[..BLOCK_LEN], ct[..BLOCK_LEN]);
+    }
+    assert_eq!(xts.encrypt_sector(0, &long[..10]), Err(PlaintextTooShort(10)));
+    assert!(matches!(Xts::new(&long[..48]), Err(InvalidKeyLength(48))));
+
+    // CMAC, RFC 4493 examples 1-4 (the first 0, 16, 40 and 64 bytes of the
+    // SP 800-38A plaintext)
+    let pt = hex::decode("6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+                          30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710").unwrap();
+    let vectors = [
+        (0, "bb1d6929e95937287fa37d129b756746"),
+        (16, "070a16b46b4d4144f79bdd9dd04a287c"),
+        (40, "dfa66747de9ae63030ca32611497c827"),
+        (64, "51f0bebf7e3b9d92fc49741779363cfe"),
+    ];
+    for (len, tag) in vectors {
+        let tag = hex::decode(tag).unwrap();
+        assert_eq!(cmac(&aes128, &pt[..len]).to_vec(), tag);
+        assert!(verify_cmac(&aes128, &pt[..len], &tag));
+        assert!(!verify_cmac(&aes128, &pt[..len], &tag[1..]));
+        if len > 0 {
+            assert!(!verify_cmac(&aes128, &pt[..len - 1], &tag));
+        }
+    }
+
+    // Raw CBC-MAC forgery: from the tags of two one-block messages, the tag
+    // of a two-block message nobody ever MACed
+    let m1 = b"Pay Alice $100.-";
+    let m2 = b"Pay Mallory $1M.";
+    let (t1, t2) = (cbc_mac(&aes128, m1), cbc_mac(&aes128, m2));
+    let mut forged = m1.to_vec();
+    forged.extend(m2.iter().zip(&t1).map(|(m, t)| m ^ t));
+    assert!(verify_cbc_mac(&aes128, &forged, &t2));
+    // the same trick does not work against CMAC
+    let t2 = cmac(&aes128, m2);
+    let mut forged = m1.to_vec();
+    forged.extend(m2.iter().zip(&cmac(&aes128, m1)).map(|(m, t)| m ^ t));
+    assert!(!verify_cmac(&aes128, &forged, &t2));
+
+    // AES-SIV, RFC 5297 A.1 (deterministic) and A.2 (nonce based)
+    let siv = Siv::new(&hex::decode("fffefdfcfbfaf9f8f7f6f5f4f3f2f1f0f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff").unwrap()).unwrap();
+    let ad = hex::decode("101112131415161718191a1b1c1d1e1f2021222324252627").unwrap();
+    let pt = hex::decode("112233445566778899aabbccddee").unwrap();
+    let ct = hex::decode("85632d07c6e8f37f950acd320a2ecc9340c02b9690c4dc04daef7f6afe5c").unwrap();
+    assert_eq!(siv.encrypt(&[&ad], &pt), ct);
+    assert_eq!(siv.decrypt(&[&ad], &ct), Ok(pt.clone()));
+
+    let siv = Siv::new(&hex::decode("7f7e7d7c7b7a797877767574737271704041424344454647\
+                                     48494a4b4c4d4e4f").unwrap()).unwrap();
+    let ad1 = hex::decode("00112233445566778899aabbccddeeffdeaddadadeaddadaffeeddccbbaa99887766554433221100").unwrap();
+    let ad2 = hex::decode("102030405060708090a0").unwrap();
+    let nonce = hex::decode("09f911029d74e35bd84156c5635688c0").unwrap();
+    let pt = hex::decode("7468697320697320736f6d6520706c61696e7465787420746f20656e6372797074\
+                          207573696e67205349562d414553").unwrap();
+    let ct = hex::decode("7bdb6e3b432667eb06f4d14bff2fbd0fcb900f2fddbe404326601965c889bf17\
+                          dba77ceb094fa663b7a3f748ba8af829ea64ad544a272e9c485b62a3fd5c0d").unwrap();
+    assert_eq!(siv.encrypt(&[&ad1, &ad2, &nonce], &pt), ct);
+    assert_eq!(siv.decrypt(&[&ad1, &ad2, &nonce], &ct), Ok(pt.clone()));
+
+    // tampering with anything, or reordering the associated data, is caught
+    let mut forged = ct.clone();
+    forged[20] ^= 1;
+    assert_eq!(siv.decrypt(&[&ad1, &ad2, &nonce], &forged), Err(DecryptError::InvalidTag));
+    assert_eq!(siv.decrypt(&[&ad2, &ad1, &nonce], &ct), Err(DecryptError::InvalidTag));
+    assert_eq!(siv.decrypt(&[&ad1, &ad2], &ct), Err(DecryptError::InvalidTag));
+    assert_eq!(siv.decrypt(&[], &ct[..8]), Err(DecryptError::MissingIv));
+
+    // a reused nonce only tells that the very same message was sent again
+    let a = siv.encrypt(&[&nonce], b"Always avoid the two time pad!");
+    let b = siv.encrypt(&[&nonce], b"Always avoid the one time pad!");
+    assert_eq!(a, siv.encrypt(&[&nonce], b"Always avoid the two time pad!"));
+    assert_ne!(a[..TAG_LEN], b[..TAG_LEN]);
+    assert!(a[TAG_LEN..].iter().zip(&b[TAG_LEN..]).filter(|(x, y)| x == y).count() < 8);
+    for len in [0, 1, 15, 16, 17, 100] {
+        let ct = siv.encrypt(&[], &long[..len]);
+        assert_eq!(siv.decrypt(&[], &ct), Ok(long[..len].to_vec()));
+    }
+    assert!(matches!(Siv::new(&long[..16]), Err(InvalidKeyLength(16))));
+
+    // PBKDF2-HMAC-SHA256, RFC 7914 section 11
+    assert_eq!(hex::encode(container::pbkdf2_sha256(b"passwd", b"salt", 1, 64)),
+               "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc\
+                49ca9cccf179b645991664b39d77ef317c71b845b1e30bd509112041d3a19783");
+
+    // containers, raw key or passphrase, with every algorithm
+    let key = &long[..64];
+    for (algorithm, key_len) in [(Algorithm::Gcm, 16), (Algorithm::Gcm, 32), (Algorithm::Siv, 32), (Algorithm::Siv, 64)] {
+        for secret in [Secret::Key(&key[..key_len]), Secret::Passphrase(b"correct horse")] {
+            let sealed = container::seal(secret, algorithm, key_len, 10, b"file.txt", &pt).unwrap();
+            let (header, opened) = container::open(secret, &sealed).unwrap();
+            assert_eq!(opened, pt);
+            assert_eq!((header.algorithm, header.key_len, &header.aad[..]), (algorithm, key_len, &b"file.txt"[..]));
+        }
+    }
 
+    // the header is authenticated along with the ciphertext
+    let sealed = container::seal(Secret::Key(&key[..32]), Algorithm::Gcm, 32, 0, b"file.txt", &pt).unwrap();
+    let (_, header_len) = Header::parse(&sealed).unwrap();
+    for i in [header_len - 1, header_len, sealed.len() - 1] {
+        let mut forged = sealed.clone();
+        forged[i] ^= 1;
+        assert_eq!(container::open(Secret::Key(&key[..32]), &forged),
+                   Err(ContainerError::Decrypt(DecryptError::InvalidTag)));
+    }
+    assert_eq!(container::open(Secret::Key(&key[32..64]), &sealed),
+               Err(ContainerError::Decrypt(DecryptError::InvalidTag)));
+    assert_eq!(container::open(Secret::Passphrase(b"correct horse"), &sealed), Err(ContainerError::WrongSecret));
+    assert_eq!(container::open(Secret::Key(&key[..32]), &sealed[..header_len + 15]), Err(ContainerError::Truncated));
+    assert_eq!(container::open(Secret::Key(&key[..32]), &sealed[..10]), Err(ContainerError::Truncated));
+    let mut forged = sealed.clone();
+    forged[0] = b'X';
+    assert_eq!(container::open(Secret::Key(&key[..32]), &forged), Err(ContainerError::BadMagic));
+    forged = sealed.clone();
+    forged[4] = 2;
+    assert_eq!(container::open(Secret::Key(&key[..32]), &forged), Err(ContainerError::UnsupportedVersion(2)));
+    assert_eq!(container::seal(Secret::Key(&key[..20]), Algorithm::Gcm, 20, 0, b"", &pt),
+               Err(ContainerError::InvalidKeyLength(20)));
+
+    // the iteration count is read before anything is authenticated,
+    // so a huge one is refused rather than spent
+    let sealed = container::seal(Secret::Passphrase(b"pass"), Algorithm::Gcm, 16, 10, b"", &pt).unwrap();
+    for iterations in [0, container::MAX_ITERATIONS + 1, u32::MAX] {
+        let mut forged = sealed.clone();
+        forged[8..12].copy_from_slice(&iterations.to_be_bytes());
+        assert_eq!(container::open(Secret::Passphrase(b"pass"), &forged),
+                   Err(ContainerError::InvalidHeader("PBKDF2 iterations")));
+        assert_eq!(container::seal(Secret::Passphrase(b"pass"), Algorithm::Gcm, 16, iterations, b"", &pt),
+                   Err(ContainerError::InvalidHeader("PBKDF2 iterations")));
+    }
+}
+
+fn print_header(header: &Header) {
+    println!("version:   {}", header.version);
+    println!("algorithm: {}", header.algorithm);
+    println!("key:       {} bytes", header.key_len);
+    match &header.kdf {
+        Kdf::Raw => { println!("kdf:       none (raw key)"); }
+        Kdf::Pbkdf2Sha256 { iterations, salt } => {
+            println!("kdf:       PBKDF2-HMAC-SHA256, {} iterations, salt {}", iterations, hex::encode(salt));
+        }
+    }
+    println!("nonce:     {}", hex::encode(&header.nonce));
+    println!("aad:       {:?}", String::from_utf8_lossy(&header.aad));
+}
+
+fn run(opts: &Options) -> Result<(), String> {
+    let input = fs::read(&opts.files[0]).map_err(|e| format!("{}: {}", opts.files[0], e))?;
+    if opts.command == Command::Info {
+        let (header, _) = Header::parse(&input).map_err(|e| format!("{}: {}", opts.files[0], e))?;
+        print_header(&header);
+        return Ok(());
+    }
+
+    let key = match &opts.key_file {
+        Some(f) => {
+            let text = fs::read_to_string(f).map_err(|e| format!("{}: {}", f, e))?;
+            hex::decode(text.trim()).map_err(|e| format!("{}: {}", f, e))?
+        }
+        None => { Vec::new() }
+    };
+    let passphrase = match &opts.passphrase_env {
+        Some(var) => { std::env::var(var).map_err(|_| format!("environment variable {} is not set", var))? }
+        None => { String::new() }
+    };
+    let secret = match opts.key_file {
+        Some(_) => { Secret::Key(&key) }
+        None => { Secret::Passphrase(passphrase.as_bytes()) }
+    };
+
+    let output = match opts.command {
+        Command::Encrypt => {
+            // a key file gives its own length, a passphrase gets key_bits
+            let key_len = match secret {
+                Secret::Key(k) => { k.len() }
+                Secret::Passphrase(_) if opts.algorithm == Algorithm::Siv => { opts.key_bits / 4 }
+                Secret::Passphrase(_) => { opts.key_bits / 8 }
+            };
+            container::seal(secret, opts.algorithm, key_len, opts.iterations, &opts.aad, &input)
+                .map_err(|e| e.to_string())?
+        }
+        _ => {
+            let (header, plaintext) = container::open(secret, &input)
+                .map_err(|e| format!("{}: {}", opts.files[0], e))?;
+            if !header.aad.is_empty() {
+                eprintln!("associated data: {:?}", String::from_utf8_lossy(&header.aad));
+            }
+            plaintext
+        }
+    };
+    fs::write(&opts.files[1], output).map_err(|e| format!("{}: {}", opts.files[1], e))
+}
+
+fn main() {
+    if std::env::args().len() == 1 {
+        self_test();
+        return;
+    }
+    let opts = match cli::parse(std::env::args().skip(1)) {
+        Ok(opts) => { opts }
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, cli::USAGE);
+            process::exit(2);
+        }
+    };
+    if opts.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+
+    if let Err(e) = run(&opts) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
 }
@@ -0,0 +1,44 @@
+use crate::{DecryptError, BLOCK_LEN};
+
+// PKCS#7: append n bytes of value n, 1 <= n <= BLOCK_LEN,
+// so that the length is a multiple of BLOCK_LEN
+pub fn pad(plaintext: &[u8]) -> Vec<u8>
+{
+    let padding = BLOCK_LEN - plaintext.len() % BLOCK_LEN;
+    let mut padded = plaintext.to_vec();
+    padded.resize(plaintext.len() + padding, padding as u8);
+    padded
+}
+
+// Length of the PKCS#7 padding at the end of a decrypted plaintext.
+// All the last BLOCK_LEN bytes are looked at whatever the padding length,
+// so that a valid and an invalid padding take the same time to check.
+// This is the check a padding oracle leaks the result of.
+pub fn padding_len(plaintext: &[u8]) -> Result<usize, DecryptError>
+{
+    if plaintext.is_empty() || !plaintext.len().is_multiple_of(BLOCK_LEN) {
+        return Err(DecryptError::BadLength(plaintext.len()));
+    }
+
+    let last = &plaintext[plaintext.len() - BLOCK_LEN..];
+    let n = last[BLOCK_LEN - 1];
+    let mut bad = (n == 0) as u8 | (n as usize > BLOCK_LEN) as u8;
+    for (i, b) in last.iter().enumerate() {
+        // only the last n bytes have to be equal to n
+        let in_padding = (BLOCK_LEN - i <= n as usize) as u8;
+        bad |= in_padding & (*b != n) as u8;
+    }
+
+    if bad != 0 {
+        return Err(DecryptError::InvalidPadding);
+    }
+    Ok(n as usize)
+}
+
+// Strip the PKCS#7 padding, failing if it is not valid
+pub fn unpad(mut plaintext: Vec<u8>) -> Result<Vec<u8>, DecryptError>
+{
+    let padding = padding_len(&plaintext)?;
+    plaintext.truncate(plaintext.len() - padding);
+    Ok(plaintext)
+}
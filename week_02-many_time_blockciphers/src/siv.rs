@@ -0,0 +1,86 @@
+// AES-SIV (RFC 5297): deterministic authenticated encryption.
+// The IV is not chosen by the caller but computed as a MAC (S2V, a CMAC
+// over every associated data string and the plaintext), then used both as
+// the tag and as the CTR IV. Reusing a nonce, or using none at all, only
+// shows whether two messages (with their associated data) are identical:
+// nothing like the two time pad of CTR or GCM.
+
+use crate::mac::{cmac, dbl};
+use crate::{apply_keystream, ct_eq, Aes, CounterLayout, DecryptError, InvalidKeyLength, BLOCK_LEN};
+
+pub const TAG_LEN: usize = BLOCK_LEN;
+
+pub struct Siv {
+    // K1, for S2V
+    mac: Aes,
+    // K2, for CTR
+    ctr: Aes,
+}
+
+impl Siv {
+    // The SIV key is the MAC key followed by the CTR key, both of the same
+    // size: 32, 48 or 64 bytes for AES-SIV-256, -384 and -512
+    pub fn new(key: &[u8]) -> Result<Self, InvalidKeyLength> {
+        if ![32, 48, 64].contains(&key.len()) {
+            return Err(InvalidKeyLength(key.len()));
+        }
+        let (k1, k2) = key.split_at(key.len() / 2);
+        Ok(Self { mac: Aes::new(k1)?, ctr: Aes::new(k2)? })
+    }
+
+    // S2V over the associated data strings then the plaintext: a CMAC of
+    // each, chained by doubling so that their order and boundaries count
+    fn s2v(&self, ad: &[&[u8]], plaintext: &[u8]) -> [u8; TAG_LEN] {
+        let mac = |data: &[u8]| u128::from_be_bytes(cmac(&self.mac, data));
+
+        let mut d = mac(&[0u8; BLOCK_LEN]);
+        for s in ad {
+            d = dbl(d) ^ mac(s);
+        }
+        if plaintext.len() >= BLOCK_LEN {
+            // xor D into the last 16 bytes
+            let mut t = plaintext.to_vec();
+            let end = t.len() - BLOCK_LEN;
+            t[end..].iter_mut().zip(d.to_be_bytes()).for_each(|(a, b)| *a ^= b);
+            return cmac(&self.mac, &t);
+        }
+        let mut padded = [0u8; BLOCK_LEN];
+        padded[..plaintext.len()].copy_from_slice(plaintext);
+        padded[plaintext.len()] = 0x80;
+        cmac(&self.mac, &(dbl(d) ^ u128::from_be_bytes(padded)).to_be_bytes())
+    }
+
+    // CTR with the IV V, two bits cleared so that implementations with a
+    // 64-bit counter addition get the same keystream
+    fn ctr(&self, v: &[u8; TAG_LEN], data: &mut [u8]) {
+        let q = u128::from_be_bytes(*v) & !(1 << 63 | 1 << 31);
+        // a full 128-bit counter never wraps within a message
+        apply_keystream(&self.ctr, CounterLayout::Full128, &q.to_be_bytes(), data, 1).unwrap();
+    }
+
+    // V || ciphertext, associated data strings are authenticated in order.
+    // For nonce based use, the nonce is the last associated data string.
+    pub fn encrypt(&self, ad: &[&[u8]], plaintext: &[u8]) -> Vec<u8> {
+        let v = self.s2v(ad, plaintext);
+        let mut out = v.to_vec();
+        let start = out.len();
+        out.extend_from_slice(plaintext);
+        self.ctr(&v, &mut out[start..]);
+        out
+    }
+
+    // Decrypt, then check that V is the S2V of what came out
+    pub fn decrypt(&self, ad: &[&[u8]], ciphertext: &[u8]) -> Result<Vec<u8>, DecryptError> {
+        if ciphertext.len() < TAG_LEN {
+            return Err(DecryptError::MissingIv);
+        }
+        let (v, body) = ciphertext.split_at(TAG_LEN);
+        let v: [u8; TAG_LEN] = v.try_into().unwrap();
+        let mut plaintext = body.to_vec();
+        self.ctr(&v, &mut plaintext);
+        if !ct_eq(&self.s2v(ad, &plaintext), &v) {
+            return Err(DecryptError::InvalidTag);
+        }
+        Ok(plaintext)
+    }
+}
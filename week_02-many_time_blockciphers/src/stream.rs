@@ -0,0 +1,288 @@
+// Streaming CBC and CTR: std::io adapters encrypting while writing and
+// decrypting while reading, in constant memory whatever the input size.
+// The output is byte for byte the same as encrypt_cbc/encrypt_ctr on the
+// whole input: IV first, then the ciphertext (PKCS#7 padded for CBC).
+
+use std::io::{self, Read, Write};
+
+use crate::{iv_or_random, padding, AesBlock, BlockCipher128, DecryptError, BLOCK_LEN};
+
+// bytes xored in one go by the CTR adapters
+const CHUNK_LEN: usize = 4096;
+
+fn invalid_data(e: DecryptError) -> io::Error
+{
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+// Fill block from r, stopping early only at end of file.
+// Returns how many bytes were read.
+fn read_block<R: Read>(r: &mut R, block: &mut [u8]) -> io::Result<usize>
+{
+    let mut n = 0;
+    while n < block.len() {
+        match r.read(&mut block[n..]) {
+            Ok(0) => { break; }
+            Ok(k) => { n += k; }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => { return Err(e); }
+        }
+    }
+    Ok(n)
+}
+
+fn read_iv<R: Read>(r: &mut R) -> io::Result<AesBlock>
+{
+    let mut iv = AesBlock::default();
+    if read_block(r, &mut iv)? < BLOCK_LEN {
+        return Err(invalid_data(DecryptError::MissingIv));
+    }
+    Ok(iv)
+}
+
+// a nonce that is not one block is refused, as by encrypt_ctr_with
+fn new_iv(nonce: Option<&[u8]>) -> io::Result<AesBlock>
+{
+    let iv = iv_or_random(nonce).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    Ok(AesBlock::clone_from_slice(&iv))
+}
+
+// CBC encryption of everything written to it. The last, padded block can
+// only be written once the input is over: call finish, or it is lost.
+pub struct CbcEncryptor<'a, C: BlockCipher128 + ?Sized, W: Write> {
+    cipher: &'a C,
+    inner: W,
+    // last ciphertext block, the IV at first
+    prev: AesBlock,
+    // plaintext not making a whole block yet
+    buf: [u8; BLOCK_LEN],
+    len: usize,
+}
+
+impl<'a, C: BlockCipher128 + ?Sized, W: Write> CbcEncryptor<'a, C, W> {
+    // writes the IV right away, a new one if the provided one is None
+    pub fn new(cipher: &'a C, mut inner: W, nonce: Option<&[u8]>) -> io::Result<Self> {
+        let prev = new_iv(nonce)?;
+        inner.write_all(&prev)?;
+        Ok(Self { cipher, inner, prev, buf: [0u8; BLOCK_LEN], len: 0 })
+    }
+
+    // C_i = Enc[k, C_(i-1) ^ M_i]
+    fn encrypt_block(&mut self, block: &[u8]) -> io::Result<()> {
+        self.prev.iter_mut().zip(block).for_each(|(c, m)| *c ^= m);
+        self.cipher.encrypt(&mut self.prev);
+        self.inner.write_all(&self.prev)
+    }
+
+    // pad and encrypt the last block, then hand back the writer
+    pub fn finish(mut self) -> io::Result<W> {
+        let padding = BLOCK_LEN - self.len;
+        let mut last = self.buf;
+        last[self.len..].fill(padding as u8);
+        self.encrypt_block(&last)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<C: BlockCipher128 + ?Sized, W: Write> Write for CbcEncryptor<'_, C, W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let mut rest = data;
+
+        // complete the pending block first
+        if self.len > 0 {
+            let take = rest.len().min(BLOCK_LEN - self.len);
+            self.buf[self.len..self.len + take].copy_from_slice(&rest[..take]);
+            self.len += take;
+            rest = &rest[take..];
+            if self.len < BLOCK_LEN {
+                return Ok(data.len());
+            }
+            let block = self.buf;
+            self.encrypt_block(&block)?;
+            self.len = 0;
+        }
+
+        let mut blocks = rest.chunks_exact(BLOCK_LEN);
+        for block in blocks.by_ref() {
+            self.encrypt_block(block)?;
+        }
+        let tail = blocks.remainder();
+        self.buf[..tail.len()].copy_from_slice(tail);
+        self.len = tail.len();
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// CBC decryption of everything read through it. A decrypted block is only
+// handed out once the next ciphertext block is seen, since the last one
+// holds the padding; invalid padding or length is an InvalidData error
+// wrapping the DecryptError.
+pub struct CbcDecryptor<'a, C: BlockCipher128 + ?Sized, R: Read> {
+    cipher: &'a C,
+    inner: R,
+    prev: AesBlock,
+    // decrypted block which may be the last one
+    held: Option<AesBlock>,
+    // decrypted bytes ready to be read: out[pos..len]
+    out: AesBlock,
+    pos: usize,
+    len: usize,
+    // ciphertext bytes read so far, IV included
+    total: usize,
+}
+
+impl<'a, C: BlockCipher128 + ?Sized, R: Read> CbcDecryptor<'a, C, R> {
+    // reads the IV right away
+    pub fn new(cipher: &'a C, mut inner: R) -> io::Result<Self> {
+        let prev = read_iv(&mut inner)?;
+        Ok(Self { cipher, inner, prev, held: None, out: AesBlock::default(), pos: 0, len: 0, total: BLOCK_LEN })
+    }
+
+    // Make more plaintext ready, returns false at the end of the plaintext
+    fn refill(&mut self) -> io::Result<bool> {
+        loop {
+            let mut block = AesBlock::default();
+            let n = read_block(&mut self.inner, &mut block)?;
+            self.total += n;
+
+            if n == 0 {
+                // end of ciphertext: the held block is the padded one
+                let last = match self.held.take() {
+                    Some(last) => { last }
+                    None if self.total == BLOCK_LEN => { return Err(invalid_data(DecryptError::BadLength(self.total))); }
+                    None => { return Ok(false); }
+                };
+                let padding = padding::padding_len(&last).map_err(invalid_data)?;
+                self.out = last;
+                self.pos = 0;
+                self.len = BLOCK_LEN - padding;
+                return Ok(true);
+            }
+            if n < BLOCK_LEN {
+                return Err(invalid_data(DecryptError::BadLength(self.total)));
+            }
+
+            // M_i = C_(i-1) ^ Dec[k, C_i]
+            let mut m = block;
+            self.cipher.decrypt(&mut m);
+            m.iter_mut().zip(self.prev.iter()).for_each(|(m, c)| *m ^= c);
+            self.prev = block;
+
+            if let Some(ready) = self.held.replace(m) {
+                self.out = ready;
+                self.pos = 0;
+                self.len = BLOCK_LEN;
+                return Ok(true);
+            }
+        }
+    }
+}
+
+impl<C: BlockCipher128 + ?Sized, R: Read> Read for CbcDecryptor<'_, C, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.len {
+            if !self.refill()? {
+                return Ok(0);
+            }
+        }
+        let n = buf.len().min(self.len - self.pos);
+        buf[..n].copy_from_slice(&self.out[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+// CTR keystream (CounterLayout::Full128), picking up in the middle of a
+// block where the last call stopped
+struct Keystream<'a, C: BlockCipher128 + ?Sized> {
+    cipher: &'a C,
+    counter: u128,
+    block: AesBlock,
+    // bytes of block already used
+    used: usize,
+}
+
+impl<'a, C: BlockCipher128 + ?Sized> Keystream<'a, C> {
+    fn new(cipher: &'a C, iv: &AesBlock) -> Self {
+        let counter = u128::from_be_bytes((*iv).into());
+        Self { cipher, counter, block: AesBlock::default(), used: BLOCK_LEN }
+    }
+
+    fn apply(&mut self, data: &mut [u8]) {
+        for b in data.iter_mut() {
+            if self.used == BLOCK_LEN {
+                self.block = AesBlock::from(self.counter.to_be_bytes());
+                self.cipher.encrypt(&mut self.block);
+                self.counter = self.counter.wrapping_add(1);
+                self.used = 0;
+            }
+            *b ^= self.block[self.used];
+            self.used += 1;
+        }
+    }
+}
+
+// CTR encryption of everything written to it, no padding so nothing is
+// held back: finish only flushes and hands back the writer
+pub struct CtrEncryptor<'a, C: BlockCipher128 + ?Sized, W: Write> {
+    keystream: Keystream<'a, C>,
+    inner: W,
+}
+
+impl<'a, C: BlockCipher128 + ?Sized, W: Write> CtrEncryptor<'a, C, W> {
+    // writes the IV right away, a new one if the provided one is None
+    pub fn new(cipher: &'a C, mut inner: W, nonce: Option<&[u8]>) -> io::Result<Self> {
+        let iv = new_iv(nonce)?;
+        inner.write_all(&iv)?;
+        Ok(Self { keystream: Keystream::new(cipher, &iv), inner })
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<C: BlockCipher128 + ?Sized, W: Write> Write for CtrEncryptor<'_, C, W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let mut chunk = [0u8; CHUNK_LEN];
+        for piece in data.chunks(CHUNK_LEN) {
+            let chunk = &mut chunk[..piece.len()];
+            chunk.copy_from_slice(piece);
+            self.keystream.apply(chunk);
+            self.inner.write_all(chunk)?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// CTR decryption of everything read through it
+pub struct CtrDecryptor<'a, C: BlockCipher128 + ?Sized, R: Read> {
+    keystream: Keystream<'a, C>,
+    inner: R,
+}
+
+impl<'a, C: BlockCipher128 + ?Sized, R: Read> CtrDecryptor<'a, C, R> {
+    // reads the IV right away
+    pub fn new(cipher: &'a C, mut inner: R) -> io::Result<Self> {
+        let iv = read_iv(&mut inner)?;
+        Ok(Self { keystream: Keystream::new(cipher, &iv), inner })
+    }
+}
+
+impl<C: BlockCipher128 + ?Sized, R: Read> Read for CtrDecryptor<'_, C, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.keystream.apply(&mut buf[..n]);
+        Ok(n)
+    }
+}
@@ -0,0 +1,101 @@
+// XTS-AES (IEEE 1619) for sector based storage: every 16-byte block of a
+// sector is encrypted as Enc[k1, M_j ^ T_j] ^ T_j, with the tweak
+// T_0 = Enc[k2, sector number] and T_(j+1) = T_j * x in GF(2^128).
+// No IV nor tag is stored, a sector encrypts to exactly its own size; a
+// sector whose length is not a multiple of 16 steals ciphertext from its
+// last full block.
+
+use crate::cts::PlaintextTooShort;
+use crate::{Aes, AesBlock, BlockCipher128, DecryptError, InvalidKeyLength, BLOCK_LEN};
+
+// x^128 = x^7 + x^2 + x + 1
+const GF_128_FDBK: u128 = 0x87;
+
+// T * x, XTS reading blocks as little endian numbers
+fn mul_x(t: u128) -> u128
+{
+    (t << 1) ^ (GF_128_FDBK & (t >> 127).wrapping_neg())
+}
+
+pub struct Xts {
+    data: Aes,
+    tweak: Aes,
+}
+
+impl Xts {
+    // The XTS key is the data key followed by the tweak key, each 16 or 32
+    // bytes long: 32 bytes for XTS-AES-128, 64 for XTS-AES-256
+    pub fn new(key: &[u8]) -> Result<Self, InvalidKeyLength> {
+        if key.len() != 32 && key.len() != 64 {
+            return Err(InvalidKeyLength(key.len()));
+        }
+        let (k1, k2) = key.split_at(key.len() / 2);
+        Ok(Self { data: Aes::new(k1)?, tweak: Aes::new(k2)? })
+    }
+
+    // tweak of the first block of a sector
+    fn first_tweak(&self, sector: u128) -> u128 {
+        let mut t = AesBlock::from(sector.to_le_bytes());
+        self.tweak.encrypt(&mut t);
+        u128::from_le_bytes(t.into())
+    }
+
+    // one block, encrypting or decrypting with the given tweak
+    fn block(&self, block: &[u8], t: u128, encrypting: bool) -> [u8; BLOCK_LEN] {
+        let x = u128::from_le_bytes(block.try_into().unwrap()) ^ t;
+        let mut d = AesBlock::from(x.to_le_bytes());
+        if encrypting {
+            self.data.encrypt(&mut d);
+        } else {
+            self.data.decrypt(&mut d);
+        }
+        (u128::from_le_bytes(d.into()) ^ t).to_le_bytes()
+    }
+
+    fn sector(&self, sector: u128, data: &[u8], encrypting: bool) -> Vec<u8> {
+        let full = data.len() / BLOCK_LEN;
+        let partial = data.len() % BLOCK_LEN;
+        // with stealing, the last full block is handled with the partial one
+        let plain_blocks = if partial == 0 { full } else { full - 1 };
+
+        let mut out: Vec<u8> = Vec::with_capacity(data.len());
+        let mut t = self.first_tweak(sector);
+        for block in data.chunks_exact(BLOCK_LEN).take(plain_blocks) {
+            out.extend_from_slice(&self.block(block, t, encrypting));
+            t = mul_x(t);
+        }
+        if partial == 0 {
+            return out;
+        }
+
+        // The last full block goes through with the tweak of the partial
+        // block when decrypting, the other way round when encrypting
+        let (t_full, t_partial) = (t, mul_x(t));
+        let (first, second) = if encrypting { (t_full, t_partial) } else { (t_partial, t_full) };
+        let last_full = &data[plain_blocks * BLOCK_LEN..(plain_blocks + 1) * BLOCK_LEN];
+        let tail = &data[(plain_blocks + 1) * BLOCK_LEN..];
+
+        // CC = block(M_(m-1)), C_m = CC[..b], C_(m-1) = block(M_m || CC[b..])
+        let cc = self.block(last_full, first, encrypting);
+        let mut pp = tail.to_vec();
+        pp.extend_from_slice(&cc[partial..]);
+        out.extend_from_slice(&self.block(&pp, second, encrypting));
+        out.extend_from_slice(&cc[..partial]);
+        out
+    }
+
+    // a sector is at least one block long, any length from there
+    pub fn encrypt_sector(&self, sector: u128, plaintext: &[u8]) -> Result<Vec<u8>, PlaintextTooShort> {
+        if plaintext.len() < BLOCK_LEN {
+            return Err(PlaintextTooShort(plaintext.len()));
+        }
+        Ok(self.sector(sector, plaintext, true))
+    }
+
+    pub fn decrypt_sector(&self, sector: u128, ciphertext: &[u8]) -> Result<Vec<u8>, DecryptError> {
+        if ciphertext.len() < BLOCK_LEN {
+            return Err(DecryptError::BadLength(ciphertext.len()));
+        }
+        Ok(self.sector(sector, ciphertext, false))
+    }
+}